@@ -111,11 +111,7 @@ pub mod input {
                 let main_display = *window_id == display.window().id();
 
                 match event {
-                    WindowEvent::CloseRequested => {
-                        if main_display {
-                            action = Action::Stop
-                        }
-                    }
+                    WindowEvent::CloseRequested if main_display => action = Action::Stop,
                     WindowEvent::CursorMoved { position, .. } => {
                         *cursor = Some(position.cast::<i32>().into());
                     }
@@ -203,7 +199,7 @@ pub mod input {
 }
 
 pub mod screenshot {
-    use crate::{write_targa, TargaImage};
+    use simple_targa::{write_targa, TargaImage, TargaMetadata, Timestamp};
 
     use glium::texture::{Texture2d, Texture2dDataSink};
     use glium::Surface;
@@ -318,108 +314,24 @@ pub mod screenshot {
 
         pub fn process_screenshots(&mut self) {
             for image_data in self.pickup_screenshots() {
-                std::thread::spawn(move || {
-                    let pixels = {
-                        let mut v = Vec::with_capacity(image_data.data.len() * 4);
-                        for (a, b, c, d) in image_data.data {
-                            v.push(a);
-                            v.push(b);
-                            v.push(c);
-                            v.push(d);
-                        }
+                let timestamp = Timestamp::now();
 
-                        v
-                    };
-
-                    write_targa(
-                        "screenshot.tga",
-                        TargaImage::new(pixels, image_data.width as u16, image_data.height as u16),
-                    )
-                    .unwrap();
+                std::thread::spawn(move || {
+                    let pixels = image_data.data
+                        .iter()
+                        .flat_map(| &(r, g, b, a) | [r, g, b, a])
+                        .collect();
+
+                    let mut image = TargaImage::new(pixels, image_data.width as u16, image_data.height as u16);
+                    image.metadata = Some(TargaMetadata {
+                        timestamp: Some(timestamp),
+                        software_id: env!("CARGO_PKG_NAME").to_string(),
+                        ..Default::default()
+                    });
+
+                    write_targa("screenshot.tga", &image).unwrap();
                 });
             }
         }
     }
 }
-
-pub mod simple_targa {
-    use std::fs::File;
-    use std::io::{Read, Result, Write};
-
-    pub struct TargaImage {
-        pub bytes: Vec<u8>,
-        pub width: u32,
-        pub height: u32,
-    }
-
-    impl TargaImage {
-        pub fn new(bytes: Vec<u8>, width: u16, height: u16) -> Self {
-            let width = width as u32;
-            let height = height as u32;
-
-            TargaImage {
-                bytes,
-                width,
-                height,
-            }
-        }
-    }
-
-    const TGA_HDR: usize = 18;
-    const TGA_WIDTH: usize = 12;
-    const TGA_HEIGHT: usize = 14;
-
-    pub fn read_targa(path: &str) -> Result<TargaImage> {
-        const COMPONENTS: usize = 16;
-
-        let mut file = File::open(path)?;
-
-        let mut data = Vec::new();
-        // read read not read
-        let _read = file.read_to_end(&mut data)?;
-
-        let components = data[COMPONENTS];
-        if components != 32 {
-            panic!("unexpected TGA format");
-        }
-        let width = data[TGA_WIDTH + 1] as u32 * 256 + data[TGA_WIDTH] as u32;
-        let height = data[TGA_HEIGHT + 1] as u32 * 256 + data[TGA_HEIGHT] as u32;
-        let mut bytes = Vec::new();
-        for i in 0..(width * height) as usize {
-            let index = TGA_HDR + 4 * i;
-
-            bytes.push(data[index + 2]);
-            bytes.push(data[index + 1]);
-            #[allow(clippy::identity_op)]
-            bytes.push(data[index + 0]);
-            bytes.push(data[index + 3]);
-        }
-
-        Ok(TargaImage {
-            bytes,
-            width,
-            height,
-        })
-    }
-
-    pub fn write_targa(path: &str, mut image: TargaImage) -> Result<()> {
-        let mut header = [0u8, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32, 0];
-        header[TGA_WIDTH] = (image.width % 256) as u8;
-        header[TGA_WIDTH + 1] = (image.width / 256) as u8;
-        header[TGA_HEIGHT] = (image.height % 256) as u8;
-        header[TGA_HEIGHT + 1] = (image.height / 256) as u8;
-
-        let mut file = File::create(path)?;
-
-        file.write_all(&header)?;
-
-        for i in 0..(image.width * image.height) as usize {
-            let index = 4 * i;
-
-            image.bytes.swap(index, index + 2);
-        }
-        file.write_all(&image.bytes)?;
-
-        Ok(())
-    }
-}
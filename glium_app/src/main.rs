@@ -9,7 +9,6 @@ pub mod engine;
 use engine::{start_loop, WindowedDisplay};
 use engine::input::{process_input, KeyboardState};
 use engine::screenshot::AsyncScreenshotTaker;
-use engine::simple_targa::{read_targa, write_targa, TargaImage};
 pub mod fxaa;
 pub mod shapes;
 use shapes::{Cube, CubeInstances, SpritesBatch, CUBE_INSTANCES, SPRITES_COUNT};
@@ -22,6 +21,7 @@ use glutin::display::{GetGlDisplay, GlDisplay};
 use winit::window::Fullscreen;
 use raw_window_handle::HasRawWindowHandle;
 use glium::{Api, Profile, Surface, Version};
+use simple_targa::{read_image_with, Origin, PixelFormat, ReadOptions, TargaError, TargaImage};
 use std::f32::consts::TAU;
 use std::fs::File;
 use std::io::Read;
@@ -116,13 +116,15 @@ fn main() {
     )
     .unwrap();
 
-    let targa = read_targa("resource/opengl.tga").unwrap();
-    let tessell_img =
-        glium::texture::RawImage2d::from_raw_rgba(targa.bytes, (targa.width, targa.height));
-    let dbg_img = glium::texture::RawImage2d::from_raw_rgba(
-        tessell_img.data.to_vec(),
-        (tessell_img.width, tessell_img.height),
-    );
+    let targa = read_image_with(
+        "resource/opengl.tga",
+        &ReadOptions { format: None, ..Default::default() },
+    ).unwrap();
+    let tessell_img = raw_image(targa);
+    let dbg_img = glium::texture::RawImage2d {
+        data: tessell_img.data.to_vec().into(),
+        ..tessell_img
+    };
     let opengl_texture =
         glium::texture::CompressedSrgbTexture2d::new(windowed_display.display(), tessell_img).unwrap();
     let debug_texture = glium::Texture2d::new(windowed_display.display(), dbg_img).unwrap();
@@ -621,13 +623,40 @@ fn main() {
     .unwrap();
 }
 
-fn read_icon(path: &str) -> std::io::Result<winit::window::Icon> {
-    let image = read_targa(path).unwrap();
+fn read_icon(path: &str) -> Result<winit::window::Icon, TargaError> {
+    // icons are expected in scanline order
+    let image = read_image_with(path, &ReadOptions { origin: Origin::TopLeft, ..Default::default() })?;
 
     let icon = winit::window::Icon::from_rgba(image.bytes, image.width, image.height).unwrap();
     Ok(icon)
 }
 
+// Uploads grayscale images as one or two channels rather than expanding them.
+fn raw_image(mut targa: TargaImage) -> glium::texture::RawImage2d<'static, u8> {
+    use glium::texture::ClientFormat;
+
+    let (targa, format) = match targa.format {
+        PixelFormat::Gray8 => (targa, ClientFormat::U8),
+        PixelFormat::GrayA8 => (targa, ClientFormat::U8U8),
+        PixelFormat::Rgb8 => (targa, ClientFormat::U8U8U8),
+        PixelFormat::Rgba8 => (targa, ClientFormat::U8U8U8U8),
+        // 32-bit files are stored blue first, which only needs reordering in place
+        PixelFormat::Bgra8 => {
+            targa.swizzle([2, 1, 0, 3]);
+            targa.format = PixelFormat::Rgba8;
+            (targa, ClientFormat::U8U8U8U8)
+        },
+        _ => (targa.convert(PixelFormat::Rgba8), ClientFormat::U8U8U8U8),
+    };
+
+    glium::texture::RawImage2d {
+        data: targa.bytes.into(),
+        width: targa.width,
+        height: targa.height,
+        format,
+    }
+}
+
 fn read_shader(path: &str) -> std::io::Result<String> {
     let mut file = File::open(path)?;
 
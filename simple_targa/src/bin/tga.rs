@@ -0,0 +1,364 @@
+use std::process::ExitCode;
+use simple_targa::compare::{compare, Tolerance};
+use simple_targa::{
+    decode_image, probe, read_image, write_image, write_targa_with, ImageFormat, ImageType, Origin, ReadOptions,
+    TargaError, TargaHeader, TargaImage, WriteOptions,
+};
+
+const USAGE: &str = "\
+usage: tga <command> <arguments>
+
+commands:
+    info <file>
+        print the header, descriptor and metadata
+    convert <input> <output> [--depth 8|16|24|32] [--rle | --raw] [--origin <corner>]
+        rewrite an image; corners are bottom-left, bottom-right, top-left and top-right
+    crop <input> <output> <x> <y> <width> <height>
+        keep the region whose top-left pixel is at column x of row y, counted from the top
+    flip <input> <output> --horizontal | --vertical
+        mirror the image
+    diff <expected> <actual> [--tolerance <n>] [--pixels <n>] [--psnr <dB>] [--output <file>]
+        count pixels with a channel differing by more than the tolerance, and
+        optionally write an image marking them in red; exits with 1 if more
+        pixels differ than allowed or the PSNR is lower than given
+
+Settings not given on the command line are kept from the input when it is a TGA.";
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(| arg | arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    let command = args.remove(0);
+
+    let result = match command.as_str() {
+        "info" => info(args),
+        "convert" => convert(args),
+        "crop" => crop(args),
+        "flip" => flip(args),
+        "diff" => diff(args),
+        _ => Err(format!("unknown command `{command}`")),
+    };
+
+    match result {
+        Ok(code) => code,
+        Err(message) => {
+            eprintln!("tga: {message}");
+            eprintln!("run `tga --help` for usage");
+            ExitCode::from(2)
+        },
+    }
+}
+
+// Removes `--name value` from `args`, returning the value.
+fn take_value(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    match args.iter().position(| arg | arg == name) {
+        Some(index) if index + 1 < args.len() => {
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Ok(Some(value))
+        },
+        Some(_) => Err(format!("{name} needs a value")),
+        None => Ok(None),
+    }
+}
+
+// Removes `--name` from `args`, returning whether it was there.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let before = args.len();
+    args.retain(| arg | arg != name);
+
+    args.len() != before
+}
+
+fn positional<const N: usize>(args: Vec<String>) -> Result<[String; N], String> {
+    if let Some(unknown) = args.iter().find(| arg | arg.starts_with("--")) {
+        return Err(format!("unknown option `{unknown}`"));
+    }
+    let count = args.len();
+
+    args.try_into().map_err(| _ | format!("expected {N} arguments, found {count}"))
+}
+
+fn number<T: std::str::FromStr>(text: &str, what: &str) -> Result<T, String> {
+    text.parse().map_err(| _ | format!("bad {what} `{text}`"))
+}
+
+fn parse_origin(text: &str) -> Result<Origin, String> {
+    match text {
+        "bottom-left" => Ok(Origin::BottomLeft),
+        "bottom-right" => Ok(Origin::BottomRight),
+        "top-left" => Ok(Origin::TopLeft),
+        "top-right" => Ok(Origin::TopRight),
+        _ => Err(format!("bad origin `{text}`")),
+    }
+}
+
+struct Loaded {
+    image: TargaImage,
+    format: ImageFormat,
+    header: Option<TargaHeader>,
+    /// Options that write a TGA back the way the input was stored, as far as the encoder can.
+    options: WriteOptions,
+}
+
+// Reads an image as stored, without converting its pixels or reordering its rows.
+fn load(path: &str) -> Result<Loaded, String> {
+    let error = | error: TargaError | format!("{path}: {error}");
+    let data = std::fs::read(path).map_err(| error | format!("{path}: {error}"))?;
+    let format = ImageFormat::detect(&data);
+    let header = match format {
+        ImageFormat::Targa => Some(probe(&data[..]).map_err(error)?),
+        _ => None,
+    };
+
+    let read_options = ReadOptions {
+        keep_indexed: true,
+        origin: header.map_or(Origin::BottomLeft, | header | header.origin),
+        format: None,
+    };
+    let image = decode_image(&data[..], &read_options).map_err(error)?;
+    let options = match header {
+        Some(header) => WriteOptions {
+            rle: header.rle,
+            depth: match (header.image_type, header.pixel_depth) {
+                (ImageType::ColorMapped, 8) if header.color_map_length <= 256 => 8,
+                (ImageType::ColorMapped, _) | (ImageType::Grayscale, 16) => 32,
+                (_, 15) => 16,
+                (_, depth) => depth,
+            },
+        },
+        None => WriteOptions::default(),
+    };
+
+    Ok(Loaded { image, format, header, options })
+}
+
+// Writes TGA with `options`, and anything else in the format its extension names.
+fn save(path: &str, image: &TargaImage, options: &WriteOptions) -> Result<(), String> {
+    let result = match ImageFormat::from_path(path) {
+        None | Some(ImageFormat::Targa) => write_targa_with(path, image, options),
+        Some(_) => write_image(path, image),
+    };
+
+    result.map_err(| error | format!("{path}: {error}"))
+}
+
+fn info(args: Vec<String>) -> Result<ExitCode, String> {
+    let [path] = positional(args)?;
+    let Loaded { image, format, header, .. } = load(&path)?;
+
+    println!("{path}: {format:?}, {}x{}, {:?} pixels", image.width, image.height, image.format);
+    if let Some(header) = header {
+        let image_type = match header.image_type {
+            ImageType::ColorMapped => "colour-mapped",
+            ImageType::Truecolor => "truecolor",
+            ImageType::Grayscale => "grayscale",
+        };
+        println!("  type:        {image_type}{}", if header.rle { ", run-length encoded" } else { "" });
+        println!("  depth:       {} bits, {} of them alpha", header.pixel_depth, header.alpha_bits);
+        println!("  origin:      {:?}", header.origin);
+        println!("  position:    {}, {}", header.x_origin, header.y_origin);
+        if header.color_map_type != 0 {
+            println!(
+                "  colour map:  {} entries of {} bits from {}",
+                header.color_map_length, header.color_map_depth, header.color_map_first,
+            );
+        }
+    }
+
+    let Some(metadata) = &image.metadata else {
+        return Ok(ExitCode::SUCCESS);
+    };
+    if !metadata.image_id.is_empty() {
+        println!("  image id:    {}", String::from_utf8_lossy(&metadata.image_id));
+    }
+    let fields = [
+        ("author", metadata.author.clone()),
+        ("job", metadata.job_name.clone()),
+        ("software", metadata.software_id.clone()),
+    ];
+    for (name, value) in fields {
+        if !value.is_empty() {
+            println!("  {name}:{}{value}", " ".repeat(12 - name.len()));
+        }
+    }
+    for (i, line) in metadata.comments.lines().enumerate() {
+        println!("  {}{line}", if i == 0 { "comments:    " } else { "             " });
+    }
+    if let Some(version) = metadata.software_version {
+        println!("  version:     {}.{:02}{}", version.0 / 100, version.0 % 100, version.1.to_string().trim());
+    }
+    if let Some(t) = metadata.timestamp {
+        println!(
+            "  timestamp:   {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            t.year, t.month, t.day, t.hour, t.minute, t.second,
+        );
+    }
+    let (hours, minutes, seconds) = metadata.job_time;
+    if (hours, minutes, seconds) != (0, 0, 0) {
+        println!("  job time:    {hours}:{minutes:02}:{seconds:02}");
+    }
+    println!("  key colour:  {:?}", metadata.key_color);
+    if metadata.pixel_aspect.1 != 0 {
+        println!("  aspect:      {}:{}", metadata.pixel_aspect.0, metadata.pixel_aspect.1);
+    }
+    if metadata.gamma.1 != 0 {
+        println!("  gamma:       {:.2}", metadata.gamma.0 as f32 / metadata.gamma.1 as f32);
+    }
+    if let Some(stamp) = &metadata.postage_stamp {
+        println!("  stamp:       {}x{}", stamp.width, stamp.height);
+    }
+    println!("  alpha:       {:?}", metadata.attribute_type);
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn convert(mut args: Vec<String>) -> Result<ExitCode, String> {
+    let depth = take_value(&mut args, "--depth")?;
+    let origin = take_value(&mut args, "--origin")?;
+    let rle = take_flag(&mut args, "--rle");
+    let raw = take_flag(&mut args, "--raw");
+    let [input, output] = positional(args)?;
+    if rle && raw {
+        return Err("--rle and --raw contradict each other".to_string());
+    }
+
+    let Loaded { mut image, mut options, .. } = load(&input)?;
+    if let Some(depth) = depth {
+        options.depth = number(&depth, "depth")?;
+    }
+    if rle || raw {
+        options.rle = rle;
+    }
+    if let Some(origin) = origin {
+        image.set_origin(parse_origin(&origin)?);
+    }
+    save(&output, &image, &options)?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn crop(args: Vec<String>) -> Result<ExitCode, String> {
+    let [input, output, x, y, width, height] = positional(args)?;
+    let Loaded { image, options, .. } = load(&input)?;
+    let (x, y) = (number(&x, "x")?, number(&y, "y")?);
+    let (width, height) = (number(&width, "width")?, number(&height, "height")?);
+
+    let origin = image.origin;
+    let mut top_left = image;
+    top_left.set_origin(Origin::TopLeft);
+    let outside = format!("{width}x{height} at {x}, {y} is not inside the {}x{} image", top_left.width, top_left.height);
+    let mut cropped = top_left
+        .sub_image(x, y, width, height)
+        .filter(| cropped | cropped.width != 0 && cropped.height != 0)
+        .ok_or(outside)?;
+    cropped.set_origin(origin);
+    save(&output, &cropped, &options)?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn flip(mut args: Vec<String>) -> Result<ExitCode, String> {
+    let horizontal = take_flag(&mut args, "--horizontal");
+    let vertical = take_flag(&mut args, "--vertical");
+    let [input, output] = positional(args)?;
+    if !horizontal && !vertical {
+        return Err("flip needs --horizontal or --vertical".to_string());
+    }
+
+    let Loaded { mut image, options, .. } = load(&input)?;
+    if horizontal {
+        image.flip_horizontal();
+    }
+    if vertical {
+        image.flip_vertical();
+    }
+    save(&output, &image, &options)?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn diff(mut args: Vec<String>) -> Result<ExitCode, String> {
+    let channel = take_value(&mut args, "--tolerance")?.map_or(Ok(0), | n | number(&n, "tolerance"))?;
+    let pixels = take_value(&mut args, "--pixels")?.map_or(Ok(0), | n | number(&n, "pixel count"))?;
+    let psnr = take_value(&mut args, "--psnr")?.map_or(Ok(0.0), | n | number(&n, "PSNR"))?;
+    let output = take_value(&mut args, "--output")?;
+    let [first, second] = positional(args)?;
+
+    let read = | path: &str | read_image(path).map_err(| error | format!("{path}: {error}"));
+    let (expected, actual) = (read(&first)?, read(&second)?);
+    let tolerance = Tolerance { channel, pixels, psnr };
+    let comparison = match compare(&expected, &actual, &tolerance) {
+        Err(error @ TargaError::SizeMismatch { .. }) => {
+            println!("{error}");
+            return Ok(ExitCode::FAILURE);
+        },
+        result => result.map_err(| error | error.to_string())?,
+    };
+
+    let [r, g, b, a] = comparison.max_error;
+    println!(
+        "{} of {} pixels differ by more than {channel}",
+        comparison.differing,
+        expected.width * expected.height,
+    );
+    println!("  max error:   {r} {g} {b} {a}");
+    let [r, g, b, a] = comparison.rmse;
+    println!("  rmse:        {r:.3} {g:.3} {b:.3} {a:.3}");
+    println!("  psnr:        {:.2} dB", comparison.psnr);
+    if let Some(output) = output {
+        save(&output, &comparison.diff, &WriteOptions::default())?;
+    }
+
+    Ok(if comparison.passed() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn options_come_out_of_the_arguments() {
+        let mut rest = args("in.tga --depth 16 out.tga --rle");
+        assert_eq!(take_value(&mut rest, "--depth").unwrap().as_deref(), Some("16"));
+        assert_eq!(take_value(&mut rest, "--origin").unwrap(), None);
+        assert!(take_flag(&mut rest, "--rle"));
+        assert!(!take_flag(&mut rest, "--raw"));
+        assert_eq!(positional::<2>(rest).unwrap(), ["in.tga", "out.tga"]);
+
+        assert_eq!(take_value(&mut args("in.tga --depth"), "--depth").unwrap_err(), "--depth needs a value");
+        assert_eq!(positional::<1>(args("in.tga --raw")).unwrap_err(), "unknown option `--raw`");
+        assert_eq!(positional::<2>(args("in.tga")).unwrap_err(), "expected 2 arguments, found 1");
+    }
+
+    #[test]
+    fn loads_keep_the_depth_they_were_stored_at() {
+        let header = | color_map_type: u8, image_type: u8, map_length: u8, map_depth: u8, depth: u8 | {
+            [0, color_map_type, image_type, 0, 0, map_length, 0, map_depth, 0, 0, 0, 0, 2, 0, 1, 0, depth, 0]
+        };
+        let files = [
+            // colour-mapped, with a map of two 24-bit entries
+            (header(1, 1, 2, 24, 8), vec![0, 0, 255, 0, 255, 0, 0, 1], 8),
+            // 15-bit truecolour, which is written back as 16
+            (header(0, 2, 0, 0, 15), vec![0x1f, 0, 0xe0, 0x03], 16),
+            (header(0, 2, 0, 0, 24), vec![1, 2, 3, 4, 5, 6], 24),
+        ];
+
+        for (i, (header, pixels, depth)) in files.into_iter().enumerate() {
+            let path = std::env::temp_dir().join(format!("tga-load-{}-{i}.tga", std::process::id()));
+            std::fs::write(&path, [&header[..], &pixels].concat()).unwrap();
+            let loaded = load(&path.to_string_lossy());
+            std::fs::remove_file(&path).unwrap();
+
+            let loaded = loaded.unwrap();
+            assert_eq!(loaded.options.depth, depth);
+            assert_eq!((loaded.image.width, loaded.image.height), (2, 1));
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Uncompressed Windows and OS/2 bitmaps of 1 to 32 bits per pixel,
+//! including 16 and 32-bit images with channel masks.
+
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use crate::{ColorMap, Origin, PixelFormat, ReadOptions, TargaError, TargaImage};
+
+const FILE_HEADER: usize = 14;
+const CORE_HEADER: usize = 12;
+const INFO_HEADER: usize = 40;
+const V4_HEADER: usize = 108;
+
+const BI_RGB: u32 = 0;
+const BI_RLE8: u32 = 1;
+const BI_RLE4: u32 = 2;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+pub fn decode<R: Read>(mut reader: R, options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    let mut data = Vec::new();
+    let _read = reader.read_to_end(&mut data)?;
+
+    decode_bmp(&data, options)
+}
+
+pub(crate) fn decode_bmp(data: &[u8], options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    let u16_at = | offset: usize | {
+        data.get(offset..(offset + 2))
+            .map(| b | u16::from_le_bytes([b[0], b[1]]))
+            .ok_or(TargaError::InvalidData("truncated BMP header"))
+    };
+    let u32_at = | offset: usize | {
+        data.get(offset..(offset + 4))
+            .map(| b | u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or(TargaError::InvalidData("truncated BMP header"))
+    };
+
+    if !data.starts_with(b"BM") {
+        return Err(TargaError::InvalidData("missing BMP signature"));
+    }
+    let pixel_offset = u32_at(10)? as usize;
+    let header_size = u32_at(FILE_HEADER)? as usize;
+
+    let (width, height, depth, compression, colors_used, entry_size) = if header_size == CORE_HEADER {
+        let width = u16_at(18)? as i32;
+        let height = u16_at(20)? as i16 as i32;
+        (width, height, u16_at(24)?, BI_RGB, 0, 3)
+    } else if header_size >= INFO_HEADER {
+        let width = u32_at(18)? as i32;
+        let height = u32_at(22)? as i32;
+        let depth = u16_at(28)?;
+        (width, height, depth, u32_at(30)?, u32_at(46)?, 4)
+    } else {
+        return Err(TargaError::InvalidData("bad BMP header size"));
+    };
+    let bad_dimensions = TargaError::BadDimensions { width: width as u32, height: height as u32 };
+    if width <= 0 || height == 0 || height == i32::MIN {
+        return Err(bad_dimensions);
+    }
+    let (width, height, origin) = if height < 0 {
+        (width as u32, height.unsigned_abs(), Origin::TopLeft)
+    } else {
+        (width as u32, height as u32, Origin::BottomLeft)
+    };
+    let count = crate::pixel_count(width, height)?;
+
+    // masks follow a plain info header, and are part of the later, longer ones
+    let mut table = FILE_HEADER + header_size;
+    let masks = match (compression, depth) {
+        (BI_RGB, 16) => [0x7c00, 0x03e0, 0x001f, 0],
+        (BI_RGB, 32) => [0xff_0000, 0xff00, 0xff, 0],
+        (BI_RGB, _) => [0; 4],
+        (BI_BITFIELDS | BI_ALPHABITFIELDS, 16 | 32) => {
+            let at = if header_size == INFO_HEADER { table } else { FILE_HEADER + INFO_HEADER };
+            let alpha = compression == BI_ALPHABITFIELDS || header_size >= 56;
+            let masks = [
+                u32_at(at)?,
+                u32_at(at + 4)?,
+                u32_at(at + 8)?,
+                if alpha { u32_at(at + 12)? } else { 0 },
+            ];
+            if header_size == INFO_HEADER {
+                table += if compression == BI_ALPHABITFIELDS { 16 } else { 12 };
+            }
+            masks
+        },
+        (BI_RLE8 | BI_RLE4, _) => return Err(TargaError::Unsupported("run-length compressed BMP")),
+        _ => return Err(TargaError::Unsupported("BMP compression method")),
+    };
+
+    let color_map = match depth {
+        1 | 4 | 8 => {
+            let length = match colors_used {
+                0 => 1 << depth,
+                used => (used as usize).min(1 << depth),
+            };
+            let entries = data
+                .get(table..)
+                .and_then(| data | data.get(..(entry_size * length)))
+                .ok_or(TargaError::ShortColorMap)?
+                .chunks_exact(entry_size)
+                .map(| bgr | [bgr[2], bgr[1], bgr[0], 255])
+                .collect();
+
+            Some(ColorMap { first_entry: 0, entries })
+        },
+        16 | 24 | 32 => None,
+        _ => return Err(TargaError::UnsupportedPixelDepth(depth as u8)),
+    };
+
+    let stride = (width as usize * depth as usize).div_ceil(32) * 4;
+    let pixels = data
+        .get(pixel_offset..)
+        .and_then(| data | data.get(..(stride * height as usize)))
+        .ok_or(TargaError::ShortPixelData)?;
+
+    let format = match depth {
+        1 | 4 | 8 => PixelFormat::Indexed8,
+        24 => PixelFormat::Rgb8,
+        _ if masks[3] != 0 => PixelFormat::Rgba8,
+        _ => PixelFormat::Rgb8,
+    };
+    let mut bytes = Vec::with_capacity(format.pixel_size() * count);
+    for row in pixels.chunks_exact(stride) {
+        match depth {
+            1 | 4 | 8 => {
+                let depth = depth as usize;
+                let mask = (1u8 << (depth - 1) << 1).wrapping_sub(1);
+                bytes.extend((0..width as usize).map(| x | {
+                    let bit = x * depth;
+                    row[bit / 8] >> (8 - depth - bit % 8) & mask
+                }));
+            },
+            24 => {
+                for bgr in row.chunks_exact(3).take(width as usize) {
+                    bytes.extend_from_slice(&[bgr[2], bgr[1], bgr[0]]);
+                }
+            },
+            _ => {
+                for pixel in row.chunks_exact(depth as usize / 8).take(width as usize) {
+                    let value = match *pixel {
+                        [lo, hi] => u16::from_le_bytes([lo, hi]) as u32,
+                        [b0, b1, b2, b3] => u32::from_le_bytes([b0, b1, b2, b3]),
+                        _ => unreachable!(),
+                    };
+                    let channels = if format == PixelFormat::Rgba8 { 4 } else { 3 };
+                    bytes.extend(masks[..channels].iter().map(| &mask | channel(value, mask)));
+                }
+            },
+        }
+    }
+
+    let image = TargaImage {
+        bytes,
+        width,
+        height,
+        format,
+        color_map,
+        origin,
+        metadata: None,
+    };
+
+    crate::apply_options(image, options)
+}
+
+// Extracts the bits under `mask` scaled to 8 bits; masks are contiguous runs of ones.
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let bits = mask.count_ones();
+    let value = ((value & mask) >> mask.trailing_zeros()) as u64;
+
+    match bits {
+        8.. => (value >> (bits - 8)) as u8,
+        _ => (value * 255 / ((1 << bits) - 1)) as u8,
+    }
+}
+
+/// Encodes `image` bottom-up, as 8-bit colour-mapped when it is grayscale or
+/// has an opaque colour map that fits, 32-bit with an alpha mask when it has
+/// alpha, and 24-bit otherwise.
+pub fn encode<W: Write>(mut writer: W, image: &TargaImage) -> Result<(), TargaError> {
+    let (width, height) = (image.width, image.height);
+    let count = crate::pixel_count(width, height)?;
+    if width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(TargaError::BadDimensions { width, height });
+    }
+    if image.bytes.len() != image.pixel_size() * count {
+        return Err(TargaError::ShortPixelData);
+    }
+
+    let palette = match (image.format, &image.color_map) {
+        (PixelFormat::Gray8, _) => Some((0..=255).map(| v | [v, v, v, 255]).collect()),
+        (PixelFormat::Indexed8, Some(map))
+            if map.first_entry == 0
+                && map.entries.len() <= 256
+                && map.entries.iter().all(| entry | entry[3] == 255) =>
+        {
+            Some(map.entries.clone())
+        },
+        _ => None,
+    };
+    let mut image = match image.format {
+        _ if palette.is_some() => Cow::Borrowed(image),
+        PixelFormat::Indexed8 | PixelFormat::Indexed16 => Cow::Owned(image.expand_color_map()),
+        format if format.has_alpha() => Cow::Owned(image.convert(PixelFormat::Bgra8)),
+        _ => Cow::Owned(image.convert(PixelFormat::Rgb8)),
+    };
+    if image.format == PixelFormat::Rgba8 {
+        // an expanded colour map
+        image = Cow::Owned(image.convert(PixelFormat::Bgra8));
+    }
+    if image.origin != Origin::BottomLeft {
+        image.to_mut().set_origin(Origin::BottomLeft);
+    }
+
+    let palette: Vec<[u8; 4]> = palette.unwrap_or_default();
+    let depth = match image.format {
+        PixelFormat::Bgra8 => 32,
+        PixelFormat::Rgb8 => 24,
+        _ => 8,
+    };
+    let header_size = if depth == 32 { V4_HEADER } else { INFO_HEADER };
+    let pixel_offset = FILE_HEADER + header_size + 4 * palette.len();
+    let stride = (width as usize * depth).div_ceil(32) * 4;
+    let file_size = u32::try_from(pixel_offset + stride * height as usize)
+        .map_err(| _ | TargaError::BadDimensions { width, height })?;
+
+    let mut header = Vec::with_capacity(pixel_offset);
+    header.extend_from_slice(b"BM");
+    header.extend_from_slice(&file_size.to_le_bytes());
+    header.extend_from_slice(&[0; 4]);
+    header.extend_from_slice(&(pixel_offset as u32).to_le_bytes());
+    header.extend_from_slice(&(header_size as u32).to_le_bytes());
+    header.extend_from_slice(&(width as i32).to_le_bytes());
+    header.extend_from_slice(&(height as i32).to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&(depth as u16).to_le_bytes());
+    header.extend_from_slice(&(if depth == 32 { BI_BITFIELDS } else { BI_RGB }).to_le_bytes());
+    header.extend_from_slice(&((stride * height as usize) as u32).to_le_bytes());
+    // 2835 pixels per metre is 72 DPI
+    header.extend_from_slice(&2835u32.to_le_bytes());
+    header.extend_from_slice(&2835u32.to_le_bytes());
+    header.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+    header.extend_from_slice(&[0; 4]);
+    if depth == 32 {
+        for mask in [0xff_0000u32, 0xff00, 0xff, 0xff00_0000] {
+            header.extend_from_slice(&mask.to_le_bytes());
+        }
+        header.extend_from_slice(b"BGRs");
+        header.resize(FILE_HEADER + V4_HEADER, 0);
+    }
+    for [r, g, b, _] in palette {
+        header.extend_from_slice(&[b, g, r, 0]);
+    }
+    writer.write_all(&header)?;
+
+    let padding = [0u8; 3];
+    for row in image.bytes.chunks_exact(image.pixel_size() * width as usize) {
+        if depth == 24 {
+            let bgr: Vec<u8> = row.chunks_exact(3).flat_map(| rgb | [rgb[2], rgb[1], rgb[0]]).collect();
+            writer.write_all(&bgr)?;
+        } else {
+            writer.write_all(row)?;
+        }
+        writer.write_all(&padding[..(stride - row.len())])?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings_round_trip() {
+        let bytes: Vec<u8> = (0..(4 * 5 * 3)).map(| i: u32 | (i * 53 % 256) as u8).collect();
+        let mut rgba = TargaImage::new(bytes, 5, 3);
+        rgba.origin = Origin::TopLeft;
+        let options = ReadOptions { keep_indexed: true, origin: Origin::TopLeft, format: None };
+
+        for format in [PixelFormat::Gray8, PixelFormat::Rgb8, PixelFormat::Bgra8] {
+            let image = rgba.convert(format);
+            let mut encoded = Vec::new();
+            encode(&mut encoded, &image).unwrap();
+
+            let decoded = decode(&encoded[..], &options).unwrap();
+            match format {
+                PixelFormat::Gray8 => assert_eq!(decoded.expand_color_map(), image.convert(PixelFormat::Rgba8)),
+                PixelFormat::Bgra8 => assert_eq!(decoded, rgba),
+                _ => assert_eq!(decoded, image),
+            }
+        }
+    }
+
+    #[test]
+    fn masks_and_small_depths_decode() {
+        // 2x2, top-down, 16-bit 565 through an info header followed by masks
+        let mut data = b"BM".to_vec();
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&66u32.to_le_bytes());
+        data.extend_from_slice(&40u32.to_le_bytes());
+        data.extend_from_slice(&2i32.to_le_bytes());
+        data.extend_from_slice(&(-2i32).to_le_bytes());
+        data.extend_from_slice(&[1, 0, 16, 0]);
+        data.extend_from_slice(&BI_BITFIELDS.to_le_bytes());
+        data.extend_from_slice(&[0; 20]);
+        for mask in [0xf800u32, 0x07e0, 0x001f] {
+            data.extend_from_slice(&mask.to_le_bytes());
+        }
+        data.extend_from_slice(&[0x00, 0xf8, 0xe0, 0x07, 0x1f, 0x00, 0xff, 0xff]);
+
+        let options = ReadOptions { origin: Origin::TopLeft, format: None, ..Default::default() };
+        let image = decode_bmp(&data, &options).unwrap();
+        assert_eq!(image.format, PixelFormat::Rgb8);
+        assert_eq!(image.bytes, [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]);
+
+        // 1-bit, bottom-up, OS/2 core header with a three-byte palette
+        let mut data = b"BM".to_vec();
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&32u32.to_le_bytes());
+        data.extend_from_slice(&12u32.to_le_bytes());
+        data.extend_from_slice(&[3, 0, 2, 0, 1, 0, 1, 0]);
+        data.extend_from_slice(&[0, 0, 0, 255, 255, 255]);
+        data.extend_from_slice(&[0b1010_0000, 0, 0, 0, 0b0100_0000, 0, 0, 0]);
+
+        let image = decode_bmp(&data, &options).unwrap();
+        assert_eq!(image.bytes.chunks(4).map(| p | p[0]).collect::<Vec<_>>(), [0, 255, 0, 255, 0, 255]);
+    }
+}
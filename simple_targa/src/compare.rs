@@ -0,0 +1,127 @@
+//! Pixel-by-pixel comparison of two images, for checking renders against
+//! golden files that may differ by small rounding errors between GL drivers.
+
+use crate::{PixelFormat, TargaError, TargaImage};
+
+/// How far an image may stray from the expected one and still pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Tolerance {
+    /// Largest difference in any channel for a pixel still to count as matching.
+    pub channel: u8,
+    /// Number of pixels allowed to differ by more than `channel`.
+    pub pixels: usize,
+    /// Lowest acceptable PSNR in decibels; zero accepts any.
+    pub psnr: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    /// Largest absolute difference in each of the red, green, blue and alpha channels.
+    pub max_error: [u8; 4],
+    /// Root mean square error of each channel.
+    pub rmse: [f64; 4],
+    /// Peak signal-to-noise ratio over all channels, infinite for identical images.
+    pub psnr: f64,
+    /// Pixels with a channel differing by more than the tolerance.
+    pub differing: usize,
+    /// The expected image dimmed to gray, with the differing pixels in red.
+    pub diff: TargaImage,
+    pub tolerance: Tolerance,
+}
+
+impl Comparison {
+    pub fn passed(&self) -> bool {
+        self.differing <= self.tolerance.pixels && self.psnr >= self.tolerance.psnr
+    }
+}
+
+/// Compares `actual` against `expected` as RGBA, whatever their formats,
+/// colour maps and row orders.
+pub fn compare(expected: &TargaImage, actual: &TargaImage, tolerance: &Tolerance) -> Result<Comparison, TargaError> {
+    if (expected.width, expected.height) != (actual.width, actual.height) {
+        return Err(TargaError::SizeMismatch {
+            expected: (expected.width, expected.height),
+            actual: (actual.width, actual.height),
+        });
+    }
+    let count = crate::pixel_count(expected.width, expected.height)?;
+    let rgba = | image: &TargaImage | -> Result<TargaImage, TargaError> {
+        if image.bytes.len() != image.pixel_size() * count {
+            return Err(TargaError::ShortPixelData);
+        }
+        let mut image = image.convert(PixelFormat::Rgba8);
+        image.set_origin(expected.origin);
+
+        Ok(image)
+    };
+    let (expected, actual) = (rgba(expected)?, rgba(actual)?);
+
+    let mut max_error = [0; 4];
+    let mut squares = [0u64; 4];
+    let mut differing = 0;
+    let mut diff = expected.clone();
+    diff.metadata = None;
+    let pairs = expected.bytes.chunks_exact(4).zip(actual.bytes.chunks_exact(4));
+    for (marked, (p, q)) in diff.bytes.chunks_exact_mut(4).zip(pairs) {
+        let mut worst = 0;
+        for channel in 0..4 {
+            let error = p[channel].abs_diff(q[channel]);
+            max_error[channel] = max_error[channel].max(error);
+            squares[channel] += error as u64 * error as u64;
+            worst = worst.max(error);
+        }
+
+        if worst > tolerance.channel {
+            differing += 1;
+            marked.copy_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let gray = ((p[0] as u16 + p[1] as u16 + p[2] as u16) / 12) as u8;
+            marked.copy_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+
+    let rmse = squares.map(| sum | (sum as f64 / count as f64).sqrt());
+    let mean_square = squares.iter().sum::<u64>() as f64 / (4 * count) as f64;
+    let psnr = if mean_square == 0.0 { f64::INFINITY } else { 10.0 * (255.0 * 255.0 / mean_square).log10() };
+
+    Ok(Comparison { max_error, rmse, psnr, differing, diff, tolerance: *tolerance })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Origin;
+
+    #[test]
+    fn errors_are_measured_per_channel() {
+        let expected = TargaImage::new(vec![10, 20, 30, 255, 0, 0, 0, 255, 200, 200, 200, 255, 5, 5, 5, 5], 2, 2);
+
+        let same = compare(&expected, &expected.convert(PixelFormat::Bgra8), &Tolerance::default()).unwrap();
+        assert_eq!((same.max_error, same.differing, same.psnr), ([0; 4], 0, f64::INFINITY));
+        assert!(same.passed());
+
+        let mut actual = expected.clone();
+        actual.bytes[0] = 14;
+        actual.bytes[10] = 190;
+        actual.bytes[15] = 7;
+        // the same pixels with the rows stored the other way up
+        actual.set_origin(Origin::TopLeft);
+        let loose = Tolerance { channel: 4, ..Default::default() };
+        let comparison = compare(&expected, &actual, &loose).unwrap();
+        assert_eq!(comparison.max_error, [4, 0, 10, 2]);
+        assert_eq!(comparison.rmse, [2.0, 0.0, 5.0, 1.0]);
+        assert!((comparison.psnr - 10.0 * (65025.0f64 / 7.5).log10()).abs() < 1e-9);
+        assert_eq!(comparison.differing, 1);
+        assert!(!comparison.passed());
+        assert_eq!(comparison.diff.bytes[8..12], [255, 0, 0, 255]);
+        assert_eq!(comparison.diff.bytes[0..4], [5, 5, 5, 255]);
+
+        let lenient = Tolerance { channel: 4, pixels: 1, psnr: 30.0 };
+        assert!(compare(&expected, &actual, &lenient).unwrap().passed());
+        let strict = Tolerance { psnr: 50.0, ..lenient };
+        assert!(!compare(&expected, &actual, &strict).unwrap().passed());
+
+        let smaller = expected.sub_image(0, 0, 2, 1).unwrap();
+        assert!(matches!(compare(&expected, &smaller, &loose), Err(TargaError::SizeMismatch { .. })));
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum TargaError {
+    Io(io::Error),
+    TruncatedHeader,
+    UnsupportedImageType(u8),
+    UnsupportedPixelDepth(u8),
+    UnsupportedColorMapDepth(u8),
+    BadDimensions { width: u32, height: u32 },
+    ShortColorMap,
+    ShortPixelData,
+    /// Malformed data in one of the other codecs, such as a bad PNG chunk.
+    InvalidData(&'static str),
+    /// A valid feature of one of the other codecs that is not implemented.
+    Unsupported(&'static str),
+    /// Images compared pixel by pixel have different dimensions.
+    SizeMismatch { expected: (u32, u32), actual: (u32, u32) },
+}
+
+impl fmt::Display for TargaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargaError::Io(error) => write!(f, "I/O error: {error}"),
+            TargaError::TruncatedHeader => write!(f, "file is shorter than the 18-byte TGA header"),
+            TargaError::UnsupportedImageType(image_type) => write!(f, "unsupported TGA image type {image_type}"),
+            TargaError::UnsupportedPixelDepth(depth) => write!(f, "unsupported pixel depth of {depth} bits"),
+            TargaError::UnsupportedColorMapDepth(depth) => write!(f, "unsupported colour map entry size of {depth} bits"),
+            TargaError::BadDimensions { width, height } => write!(f, "bad image dimensions {width}x{height}"),
+            TargaError::ShortColorMap => write!(f, "colour map ends before its declared length"),
+            TargaError::ShortPixelData => write!(f, "pixel data ends before the declared image size"),
+            TargaError::InvalidData(problem) => write!(f, "invalid image data: {problem}"),
+            TargaError::Unsupported(feature) => write!(f, "unsupported feature: {feature}"),
+            TargaError::SizeMismatch { expected, actual } => {
+                write!(f, "expected a {}x{} image, found {}x{}", expected.0, expected.1, actual.0, actual.1)
+            },
+        }
+    }
+}
+
+impl std::error::Error for TargaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TargaError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TargaError {
+    fn from(error: io::Error) -> Self {
+        TargaError::Io(error)
+    }
+}
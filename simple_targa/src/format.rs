@@ -0,0 +1,110 @@
+/// Layout of each pixel in `TargaImage::bytes`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    Gray8,
+    GrayA8,
+    Rgb8,
+    #[default]
+    Rgba8,
+    Bgra8,
+    /// Little-endian `u16` with red in the top five bits, as GL's `UNSIGNED_SHORT_5_6_5`.
+    Rgb565,
+    /// Little-endian `u16` with alpha in the lowest bit, as GL's `UNSIGNED_SHORT_5_5_5_1`.
+    Rgba5551,
+    /// Index into the image's colour map.
+    Indexed8,
+    /// Little-endian index into the image's colour map.
+    Indexed16,
+}
+
+impl PixelFormat {
+    pub fn pixel_size(self) -> usize {
+        match self {
+            PixelFormat::Gray8 | PixelFormat::Indexed8 => 1,
+            PixelFormat::GrayA8 | PixelFormat::Rgb565 | PixelFormat::Rgba5551 | PixelFormat::Indexed16 => 2,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 => 4,
+        }
+    }
+
+    pub fn is_indexed(self) -> bool {
+        matches!(self, PixelFormat::Indexed8 | PixelFormat::Indexed16)
+    }
+
+    pub fn has_alpha(self) -> bool {
+        matches!(self, PixelFormat::GrayA8 | PixelFormat::Rgba8 | PixelFormat::Bgra8 | PixelFormat::Rgba5551)
+    }
+
+    pub fn is_gray(self) -> bool {
+        matches!(self, PixelFormat::Gray8 | PixelFormat::GrayA8)
+    }
+
+    /// Widens one pixel to RGBA.
+    ///
+    /// # Panics
+    ///
+    /// Panics for indexed formats, which need a colour map
+    pub(crate) fn to_rgba(self, pixel: &[u8]) -> [u8; 4] {
+        match self {
+            PixelFormat::Gray8 => [pixel[0], pixel[0], pixel[0], 255],
+            PixelFormat::GrayA8 => [pixel[0], pixel[0], pixel[0], pixel[1]],
+            PixelFormat::Rgb8 => [pixel[0], pixel[1], pixel[2], 255],
+            PixelFormat::Rgba8 => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            PixelFormat::Bgra8 => [pixel[2], pixel[1], pixel[0], pixel[3]],
+            PixelFormat::Rgb565 => {
+                let packed = u16::from_le_bytes([pixel[0], pixel[1]]);
+
+                [expand_bits(packed >> 11, 5), expand_bits(packed >> 5, 6), expand_bits(packed, 5), 255]
+            },
+            PixelFormat::Rgba5551 => {
+                let packed = u16::from_le_bytes([pixel[0], pixel[1]]);
+
+                [
+                    expand_bits(packed >> 11, 5),
+                    expand_bits(packed >> 6, 5),
+                    expand_bits(packed >> 1, 5),
+                    if packed & 1 != 0 { 255 } else { 0 },
+                ]
+            },
+            PixelFormat::Indexed8 | PixelFormat::Indexed16 => panic!("cannot convert from an indexed format"),
+        }
+    }
+
+    /// Narrows one RGBA pixel onto `bytes`, undoing `to_rgba` exactly for any pixel it produced.
+    ///
+    /// # Panics
+    ///
+    /// Panics for indexed formats, which need a colour map
+    pub(crate) fn push_rgba(self, [r, g, b, a]: [u8; 4], bytes: &mut Vec<u8>) {
+        match self {
+            PixelFormat::Gray8 => bytes.push(luma(r, g, b)),
+            PixelFormat::GrayA8 => bytes.extend_from_slice(&[luma(r, g, b), a]),
+            PixelFormat::Rgb8 => bytes.extend_from_slice(&[r, g, b]),
+            PixelFormat::Rgba8 => bytes.extend_from_slice(&[r, g, b, a]),
+            PixelFormat::Bgra8 => bytes.extend_from_slice(&[b, g, r, a]),
+            PixelFormat::Rgb565 => {
+                let packed = (r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | b as u16 >> 3;
+
+                bytes.extend_from_slice(&packed.to_le_bytes());
+            },
+            PixelFormat::Rgba5551 => {
+                let packed = (r as u16 >> 3) << 11 | (g as u16 >> 3) << 6 | (b as u16 >> 3) << 1 | (a >= 128) as u16;
+
+                bytes.extend_from_slice(&packed.to_le_bytes());
+            },
+            PixelFormat::Indexed8 | PixelFormat::Indexed16 => panic!("cannot convert to an indexed format"),
+        }
+    }
+}
+
+/// Scales the low `bits` bits of `value` to the full 0..=255 range by bit replication.
+pub(crate) fn expand_bits(value: u16, bits: u32) -> u8 {
+    let value = value & ((1 << bits) - 1);
+
+    ((value << (8 - bits)) | (value >> (2 * bits - 8))) as u8
+}
+
+// Rec. 709 weights summing to 256, so grays pass through unchanged.
+fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((54 * r as u32 + 183 * g as u32 + 19 * b as u32 + 128) >> 8) as u8
+}
@@ -0,0 +1,310 @@
+use crate::{PixelFormat, TargaMetadata};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TargaImage {
+    pub bytes: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+    pub color_map: Option<ColorMap>,
+    pub origin: Origin,
+    pub metadata: Option<TargaMetadata>,
+}
+
+impl TargaImage {
+    /// Wraps RGBA pixels.
+    pub fn new(bytes: Vec<u8>, width: u16, height: u16) -> Self {
+        let width = width as u32;
+        let height = height as u32;
+
+        TargaImage {
+            bytes,
+            width,
+            height,
+            format: PixelFormat::Rgba8,
+            color_map: None,
+            origin: Origin::BottomLeft,
+            metadata: None,
+        }
+    }
+
+    pub fn pixel_size(&self) -> usize {
+        self.format.pixel_size()
+    }
+
+    /// Pixel at column `x` of row `y`, counted in storage order from `origin`.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<&[u8]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let pixel_size = self.pixel_size();
+        let index = pixel_size * (y as usize * self.width as usize + x as usize);
+
+        self.bytes.get(index..(index + pixel_size))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the position is outside the image or `pixel` is not `pixel_size` bytes
+    pub fn put_pixel(&mut self, x: u32, y: u32, pixel: &[u8]) {
+        assert!(x < self.width && y < self.height, "pixel ({x}, {y}) outside the image");
+        let pixel_size = self.pixel_size();
+        let index = pixel_size * (y as usize * self.width as usize + x as usize);
+
+        self.bytes[index..(index + pixel_size)].copy_from_slice(pixel);
+    }
+
+    /// Copies out the region starting at column `x` of row `y`, or `None` if
+    /// it does not fit inside the image.
+    pub fn sub_image(&self, x: u32, y: u32, width: u32, height: u32) -> Option<TargaImage> {
+        if x.checked_add(width)? > self.width || y.checked_add(height)? > self.height {
+            return None;
+        }
+        let pixel_size = self.pixel_size();
+        let row_size = pixel_size * self.width as usize;
+
+        let mut bytes = Vec::with_capacity(pixel_size * (width * height) as usize);
+        for row in self.bytes.chunks_exact(row_size).skip(y as usize).take(height as usize) {
+            bytes.extend_from_slice(&row[(pixel_size * x as usize)..(pixel_size * (x + width) as usize)]);
+        }
+
+        Some(TargaImage {
+            bytes,
+            width,
+            height,
+            format: self.format,
+            color_map: self.color_map.clone(),
+            origin: self.origin,
+            metadata: None,
+        })
+    }
+
+    pub fn flip_vertical(&mut self) {
+        let height = self.height as usize;
+        let row_size = self.pixel_size() * self.width as usize;
+        if row_size == 0 || self.bytes.len() < row_size * height {
+            return;
+        }
+
+        for row in 0..(height / 2) {
+            let (upper, lower) = self.bytes.split_at_mut((height - 1 - row) * row_size);
+            upper[(row * row_size)..((row + 1) * row_size)].swap_with_slice(&mut lower[..row_size]);
+        }
+    }
+
+    pub fn flip_horizontal(&mut self) {
+        let width = self.width as usize;
+        let pixel_size = self.pixel_size();
+        if width == 0 || pixel_size == 0 {
+            return;
+        }
+
+        for row in self.bytes.chunks_exact_mut(pixel_size * width) {
+            for column in 0..(width / 2) {
+                let (left, right) = row.split_at_mut((width - 1 - column) * pixel_size);
+                left[(column * pixel_size)..((column + 1) * pixel_size)].swap_with_slice(&mut right[..pixel_size]);
+            }
+        }
+    }
+
+    /// Reorders rows and columns so the first pixel in `bytes` is the one at `origin`.
+    pub fn set_origin(&mut self, origin: Origin) {
+        if self.origin.is_top() != origin.is_top() {
+            self.flip_vertical();
+        }
+        if self.origin.is_right() != origin.is_right() {
+            self.flip_horizontal();
+        }
+
+        self.origin = origin;
+    }
+
+    /// Rearranges the channels of every four-channel pixel, each new channel
+    /// `i` taking the old channel `order[i]`; `[2, 1, 0, 3]` swaps red and blue.
+    /// The format is left alone, so swizzling is also how to relabel bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless the format is `Rgba8` or `Bgra8`
+    pub fn swizzle(&mut self, order: [usize; 4]) {
+        assert!(
+            matches!(self.format, PixelFormat::Rgba8 | PixelFormat::Bgra8),
+            "cannot swizzle {:?} pixels", self.format,
+        );
+
+        for pixel in self.bytes.chunks_exact_mut(4) {
+            let old = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            for (channel, &from) in pixel.iter_mut().zip(&order) {
+                *channel = old[from];
+            }
+        }
+    }
+
+    /// Converts every pixel to `format`. Conversions that widen pixels, and
+    /// narrowing them back again, are exact; indexed images are looked up in
+    /// their colour map first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `format` is indexed, as that would need a colour map
+    pub fn convert(&self, format: PixelFormat) -> TargaImage {
+        if format == self.format {
+            return self.clone();
+        }
+        if self.format.is_indexed() {
+            return self.expand_color_map().convert(format);
+        }
+
+        let mut bytes = Vec::with_capacity(format.pixel_size() * (self.width * self.height) as usize);
+        for pixel in self.bytes.chunks_exact(self.format.pixel_size()) {
+            format.push_rgba(self.format.to_rgba(pixel), &mut bytes);
+        }
+
+        TargaImage {
+            bytes,
+            format,
+            ..self.clone_without_pixels()
+        }
+    }
+
+    /// Looks every index up in the colour map, giving an RGBA image.
+    /// Indices outside the map become transparent black.
+    ///
+    /// Images that are not indexed are copied as they are.
+    pub fn expand_color_map(&self) -> TargaImage {
+        let empty = ColorMap { first_entry: 0, entries: Vec::new() };
+        let color_map = match self.format {
+            PixelFormat::Indexed8 | PixelFormat::Indexed16 => self.color_map.as_ref().unwrap_or(&empty),
+            _ => return self.clone(),
+        };
+
+        let mut bytes = Vec::with_capacity(4 * (self.width * self.height) as usize);
+        for index in self.bytes.chunks_exact(self.format.pixel_size()) {
+            let index = match *index {
+                [lo] => lo as usize,
+                [lo, hi] => u16::from_le_bytes([lo, hi]) as usize,
+                _ => unreachable!(),
+            };
+            let entry = index
+                .checked_sub(color_map.first_entry as usize)
+                .and_then(| i | color_map.entries.get(i))
+                .unwrap_or(&[0; 4]);
+
+            bytes.extend_from_slice(entry);
+        }
+
+        TargaImage {
+            bytes,
+            format: PixelFormat::Rgba8,
+            ..self.clone_without_pixels()
+        }
+    }
+
+    pub(crate) fn clone_without_pixels(&self) -> TargaImage {
+        TargaImage {
+            bytes: Vec::new(),
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            color_map: None,
+            origin: self.origin,
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+/// Corner of the image holding the first pixel, and so the row and column order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Origin {
+    /// OpenGL texture order, and what most TGA writers produce.
+    #[default]
+    BottomLeft,
+    BottomRight,
+    /// Scanline order, as expected by window icons.
+    TopLeft,
+    TopRight,
+}
+
+impl Origin {
+    pub(crate) fn from_descriptor(descriptor: u8) -> Self {
+        match (descriptor >> 4) & 0x03 {
+            0 => Origin::BottomLeft,
+            1 => Origin::BottomRight,
+            2 => Origin::TopLeft,
+            _ => Origin::TopRight,
+        }
+    }
+
+    pub(crate) fn descriptor_bits(self) -> u8 {
+        match self {
+            Origin::BottomLeft => 0x00,
+            Origin::BottomRight => 0x10,
+            Origin::TopLeft => 0x20,
+            Origin::TopRight => 0x30,
+        }
+    }
+
+    pub fn is_top(self) -> bool {
+        matches!(self, Origin::TopLeft | Origin::TopRight)
+    }
+
+    pub fn is_right(self) -> bool {
+        matches!(self, Origin::BottomRight | Origin::TopRight)
+    }
+}
+
+/// RGBA palette of an `Indexed8` or `Indexed16` image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorMap {
+    pub first_entry: u16,
+    pub entries: Vec<[u8; 4]>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_are_read_and_written_in_storage_order() {
+        let mut image = TargaImage::new((0..24).collect(), 3, 2);
+        assert_eq!(image.get_pixel(1, 1), Some(&[16, 17, 18, 19][..]));
+        assert_eq!(image.get_pixel(3, 0), None);
+        assert_eq!(image.get_pixel(0, 2), None);
+
+        image.put_pixel(2, 0, &[1, 2, 3, 4]);
+        assert_eq!(image.bytes[8..12], [1, 2, 3, 4]);
+
+        let gray = TargaImage { bytes: vec![5, 6, 7, 8], format: PixelFormat::GrayA8, ..TargaImage::new(Vec::new(), 2, 1) };
+        assert_eq!(gray.get_pixel(1, 0), Some(&[7, 8][..]));
+    }
+
+    #[test]
+    fn regions_and_flips_move_whole_pixels() {
+        let mut image = TargaImage::new((0..24).collect(), 3, 2);
+        let region = image.sub_image(1, 0, 2, 2).unwrap();
+        assert_eq!((region.width, region.height), (2, 2));
+        assert_eq!(region.bytes, [4, 5, 6, 7, 8, 9, 10, 11, 16, 17, 18, 19, 20, 21, 22, 23]);
+        assert!(image.sub_image(2, 0, 2, 1).is_none());
+        assert!(image.sub_image(0, 1, 1, u32::MAX).is_none());
+
+        image.flip_horizontal();
+        assert_eq!(image.get_pixel(0, 0), Some(&[8, 9, 10, 11][..]));
+        image.flip_vertical();
+        assert_eq!(image.get_pixel(0, 0), Some(&[20, 21, 22, 23][..]));
+    }
+
+    #[test]
+    fn swizzling_reorders_channels() {
+        let mut image = TargaImage::new(vec![1, 2, 3, 4, 5, 6, 7, 8], 2, 1);
+        image.swizzle([2, 1, 0, 3]);
+        assert_eq!(image.bytes, [3, 2, 1, 4, 7, 6, 5, 8]);
+        image.swizzle([3, 3, 3, 0]);
+        assert_eq!(image.bytes, [4, 4, 4, 3, 8, 8, 8, 7]);
+
+        // swizzling Bgra8 to red first matches converting it
+        let bgra = TargaImage { format: PixelFormat::Bgra8, ..TargaImage::new(vec![1, 2, 3, 4], 1, 1) };
+        let mut swizzled = bgra.clone();
+        swizzled.swizzle([2, 1, 0, 3]);
+        assert_eq!(swizzled.bytes, bgra.convert(PixelFormat::Rgba8).bytes);
+    }
+}
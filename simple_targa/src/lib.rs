@@ -1,77 +1,1111 @@
 use std::fs::File;
-use std::io::{Read, Result, Write};
+use std::borrow::Cow;
+use std::io::{BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+pub mod compare;
+mod error;
+pub use error::TargaError;
+mod format;
+pub use format::PixelFormat;
+mod image;
+pub use image::{ColorMap, Origin, TargaImage};
+mod metadata;
+pub use metadata::{AttributeType, TargaMetadata, Timestamp};
+mod process;
+pub use process::{Filter, ResizeOptions};
+pub mod bmp;
+pub mod png;
+pub mod pnm;
+mod zlib;
 
-pub struct TargaImage {
-    pub bytes: Vec<u8>,
-    pub width: u32,
-    pub height: u32,
+#[derive(Clone, Copy, Debug)]
+pub struct ReadOptions {
+    /// Keep colour-mapped images as indices plus a `ColorMap` rather than converting them to `format`.
+    pub keep_indexed: bool,
+    /// Row order of the returned image, whatever order the file stores.
+    pub origin: Origin,
+    /// Pixel format of the returned image, or `None` for the closest match
+    /// to the file: `Gray8` for 8-bit grayscale, `Bgra8` for 32-bit truecolor,
+    /// `Rgba8` for expanded colour maps and so on. Indexed formats are only
+    /// given for colour-mapped images kept as indices; asking for one when
+    /// the image is not is an error.
+    pub format: Option<PixelFormat>,
 }
 
-impl TargaImage {
-    pub fn new(bytes: Vec<u8>, width: u16, height: u16) -> Self {
-        let width = width as u32;
-        let height = height as u32;
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            keep_indexed: false,
+            origin: Origin::BottomLeft,
+            format: Some(PixelFormat::Rgba8),
+        }
+    }
+}
+
+const TGA_HDR: usize = 18;
+const TGA_ID_LENGTH: usize = 0;
+const TGA_MAP_TYPE: usize = 1;
+const TGA_TYPE: usize = 2;
+const TGA_MAP_FIRST: usize = 3;
+const TGA_MAP_LENGTH: usize = 5;
+const TGA_MAP_DEPTH: usize = 7;
+const TGA_X_ORIGIN: usize = 8;
+const TGA_Y_ORIGIN: usize = 10;
+const TGA_WIDTH: usize = 12;
+const TGA_HEIGHT: usize = 14;
+const TGA_DEPTH: usize = 16;
+const TGA_DESCRIPTOR: usize = 17;
+
+const COLOR_MAPPED: u8 = 1;
+const TRUECOLOR: u8 = 2;
+const GRAYSCALE: u8 = 3;
+const RLE_COLOR_MAPPED: u8 = 9;
+const RLE_TRUECOLOR: u8 = 10;
+const RLE_GRAYSCALE: u8 = 11;
+
+/// The fixed 18-byte header, enough to size and classify an image without decoding it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TargaHeader {
+    pub id_length: u8,
+    pub color_map_type: u8,
+    pub image_type: ImageType,
+    pub rle: bool,
+    pub color_map_first: u16,
+    pub color_map_length: u16,
+    pub color_map_depth: u8,
+    pub x_origin: u16,
+    pub y_origin: u16,
+    pub width: u16,
+    pub height: u16,
+    pub pixel_depth: u8,
+    pub alpha_bits: u8,
+    pub origin: Origin,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageType {
+    ColorMapped,
+    Truecolor,
+    Grayscale,
+}
+
+impl TargaHeader {
+    fn parse(header: &[u8]) -> Result<Self, TargaError> {
+        let header = header.get(..TGA_HDR).ok_or(TargaError::TruncatedHeader)?;
+        let u16_at = | offset: usize | u16::from_le_bytes([header[offset], header[offset + 1]]);
+
+        let (image_type, rle) = match header[TGA_TYPE] {
+            COLOR_MAPPED => (ImageType::ColorMapped, false),
+            TRUECOLOR => (ImageType::Truecolor, false),
+            GRAYSCALE => (ImageType::Grayscale, false),
+            RLE_COLOR_MAPPED => (ImageType::ColorMapped, true),
+            RLE_TRUECOLOR => (ImageType::Truecolor, true),
+            RLE_GRAYSCALE => (ImageType::Grayscale, true),
+            image_type => return Err(TargaError::UnsupportedImageType(image_type)),
+        };
+        let pixel_depth = header[TGA_DEPTH];
+        match (image_type, pixel_depth) {
+            (ImageType::ColorMapped, 8 | 16)
+            | (ImageType::Truecolor, 15 | 16 | 24 | 32)
+            | (ImageType::Grayscale, 8 | 16) => (),
+            _ => return Err(TargaError::UnsupportedPixelDepth(pixel_depth)),
+        }
+
+        Ok(TargaHeader {
+            id_length: header[TGA_ID_LENGTH],
+            color_map_type: header[TGA_MAP_TYPE],
+            image_type,
+            rle,
+            color_map_first: u16_at(TGA_MAP_FIRST),
+            color_map_length: u16_at(TGA_MAP_LENGTH),
+            color_map_depth: header[TGA_MAP_DEPTH],
+            x_origin: u16_at(TGA_X_ORIGIN),
+            y_origin: u16_at(TGA_Y_ORIGIN),
+            width: u16_at(TGA_WIDTH),
+            height: u16_at(TGA_HEIGHT),
+            pixel_depth,
+            alpha_bits: header[TGA_DESCRIPTOR] & 0x0f,
+            origin: Origin::from_descriptor(header[TGA_DESCRIPTOR]),
+        })
+    }
+
+    /// Bytes per stored pixel, before any expansion to RGBA.
+    pub fn pixel_size(&self) -> usize {
+        (self.pixel_depth as usize).div_ceil(8)
+    }
+}
+
+pub fn read_targa(path: &str) -> Result<TargaImage, TargaError> {
+    read_targa_with(path, &ReadOptions::default())
+}
+
+pub fn read_targa_with(path: &str, options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    decode(File::open(path)?, options)
+}
+
+pub fn probe_targa(path: &str) -> Result<TargaHeader, TargaError> {
+    probe(File::open(path)?)
+}
+
+/// Decodes a whole TGA stream; the reader is consumed to its end, as the
+/// TGA 2.0 footer can only be found from there.
+pub fn decode<R: Read>(mut reader: R, options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    let mut data = Vec::new();
+    let _read = reader.read_to_end(&mut data)?;
+
+    decode_targa(&data, options)
+}
+
+/// Reads only the header, leaving the reader just past it.
+pub fn probe<R: Read>(mut reader: R) -> Result<TargaHeader, TargaError> {
+    let mut header = [0u8; TGA_HDR];
+    reader.read_exact(&mut header).map_err(| error | match error.kind() {
+        ErrorKind::UnexpectedEof => TargaError::TruncatedHeader,
+        _ => error.into(),
+    })?;
+
+    TargaHeader::parse(&header)
+}
+
+/// File formats the crate can read and write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Targa,
+    Png,
+    Bmp,
+    /// Binary or ASCII PBM, PGM or PPM.
+    Pnm,
+}
+
+impl ImageFormat {
+    /// Identifies a file from its first bytes. TGA has no magic number, so
+    /// anything else is taken to be TGA; none of the other signatures can
+    /// begin a valid TGA header, as their second byte is never a colour map type.
+    pub fn detect(data: &[u8]) -> ImageFormat {
+        match data {
+            [0x89, b'P', b'N', b'G', ..] => ImageFormat::Png,
+            [b'B', b'M', ..] => ImageFormat::Bmp,
+            [b'P', b'1'..=b'6', ..] => ImageFormat::Pnm,
+            _ => ImageFormat::Targa,
+        }
+    }
+
+    /// Guesses the format from the extension of a file name.
+    pub fn from_path(path: &str) -> Option<ImageFormat> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "tga" | "targa" | "icb" | "vda" | "vst" => Some(ImageFormat::Targa),
+            "png" => Some(ImageFormat::Png),
+            "bmp" | "dib" => Some(ImageFormat::Bmp),
+            "pbm" | "pgm" | "ppm" | "pnm" => Some(ImageFormat::Pnm),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a TGA, PNG, BMP or PNM file, whatever its name says.
+pub fn read_image(path: &str) -> Result<TargaImage, TargaError> {
+    read_image_with(path, &ReadOptions::default())
+}
+
+pub fn read_image_with(path: &str, options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    decode_image(File::open(path)?, options)
+}
+
+/// Decodes a stream in any supported format, chosen by `ImageFormat::detect`.
+pub fn decode_image<R: Read>(mut reader: R, options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    let mut data = Vec::new();
+    let _read = reader.read_to_end(&mut data)?;
+
+    match ImageFormat::detect(&data) {
+        ImageFormat::Targa => decode_targa(&data, options),
+        ImageFormat::Png => png::decode_png(&data, options),
+        ImageFormat::Bmp => bmp::decode_bmp(&data, options),
+        ImageFormat::Pnm => pnm::decode_pnm(&data, options),
+    }
+}
+
+/// Writes `image` in the format its extension names, or as TGA if it names none.
+pub fn write_image(path: &str, image: &TargaImage) -> Result<(), TargaError> {
+    let format = ImageFormat::from_path(path).unwrap_or(ImageFormat::Targa);
+
+    encode_image(BufWriter::new(File::create(path)?), image, format)
+}
+
+/// Encodes `image` with the default settings of `format`; the codec
+/// modules offer more control.
+pub fn encode_image<W: Write>(writer: W, image: &TargaImage, format: ImageFormat) -> Result<(), TargaError> {
+    match format {
+        ImageFormat::Targa => encode(writer, image, &WriteOptions::default()),
+        ImageFormat::Png => png::encode(writer, image),
+        ImageFormat::Bmp => bmp::encode(writer, image),
+        ImageFormat::Pnm => pnm::encode(writer, image),
+    }
+}
+
+fn decode_targa(data: &[u8], options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    let header = TargaHeader::parse(data)?;
+
+    let pixel_size = header.pixel_size();
+    let alpha_bits = header.alpha_bits;
+    let width = header.width as u32;
+    let height = header.height as u32;
+    let count = pixel_count(width, height)?;
+
+    let id_length = header.id_length as usize;
+    let image_id = data.get(TGA_HDR..(TGA_HDR + id_length)).ok_or(TargaError::TruncatedHeader)?;
+    let mut offset = TGA_HDR + id_length;
+
+    let map_depth = header.color_map_depth;
+    let entry_size = (map_depth as usize).div_ceil(8);
+    // truecolor and grayscale images may carry a colour map that is only skipped
+    let map_data = if header.color_map_type != 0 {
+        data.get(offset..(offset + entry_size * header.color_map_length as usize))
+            .ok_or(TargaError::ShortColorMap)?
+    } else {
+        &[]
+    };
+    offset += map_data.len();
+
+    let color_map = if header.image_type == ImageType::ColorMapped {
+        // 16-bit entries only carry alpha when the descriptor claims an attribute bit
+        let map_alpha_bits = if map_depth == 16 { alpha_bits } else { 0 };
+        let entries = match map_depth {
+            15 | 16 | 24 | 32 => map_data
+                .chunks_exact(entry_size)
+                .map(| entry | map_entry(entry, map_alpha_bits))
+                .collect(),
+            _ => return Err(TargaError::UnsupportedColorMapDepth(map_depth)),
+        };
+
+        Some(ColorMap {
+            first_entry: header.color_map_first,
+            entries,
+        })
+    } else {
+        None
+    };
+    let format = FileFormat {
+        format: match (header.image_type, header.pixel_depth) {
+            (ImageType::ColorMapped, 8) => PixelFormat::Indexed8,
+            (ImageType::ColorMapped, _) => PixelFormat::Indexed16,
+            (ImageType::Grayscale, 8) => PixelFormat::Gray8,
+            (ImageType::Grayscale, _) => PixelFormat::GrayA8,
+            (ImageType::Truecolor, 15 | 16) => PixelFormat::Rgba5551,
+            (ImageType::Truecolor, 24) => PixelFormat::Rgb8,
+            (ImageType::Truecolor, _) => PixelFormat::Bgra8,
+        },
+        alpha_bits,
+        color_map: color_map.as_ref(),
+        origin: header.origin,
+    };
+
+    let decoded;
+    let pixels = if header.rle {
+        decoded = decode_rle(&data[offset..], pixel_size, count)?;
+        &decoded[..]
+    } else {
+        data.get(offset..)
+            .and_then(| data | data.get(..(pixel_size * count)))
+            .ok_or(TargaError::ShortPixelData)?
+    };
+
+    let mut image = apply_options(format.image(pixels, width, height), options)?;
+
+    let extension = metadata::extension_offset(data)
+        .and_then(| offset | metadata::parse_extension(data, offset));
+    image.metadata = match extension {
+        Some((mut metadata, stamp_offset)) => {
+            metadata.image_id = image_id.to_vec();
+            metadata.postage_stamp = format.postage_stamp(data, stamp_offset).map(| mut stamp | {
+                stamp.set_origin(options.origin);
+                Box::new(stamp)
+            });
+            Some(metadata)
+        },
+        None if !image_id.is_empty() => Some(TargaMetadata {
+            image_id: image_id.to_vec(),
+            ..Default::default()
+        }),
+        None => None,
+    };
+
+    Ok(image)
+}
+
+/// Number of pixels in an image of the given size, refusing empty images
+/// and any whose pixels cannot be counted in a `u32` or their RGBA bytes in a `usize`.
+pub(crate) fn pixel_count(width: u32, height: u32) -> Result<usize, TargaError> {
+    width
+        .checked_mul(height)
+        .map(| count | count as usize)
+        .filter(| &count | count != 0 && count.checked_mul(4).is_some())
+        .ok_or(TargaError::BadDimensions { width, height })
+}
+
+/// Brings an image in its file's native layout to what `options` asks for,
+/// failing if that is an indexed format the image is not already in.
+pub(crate) fn apply_options(mut image: TargaImage, options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    if image.format.is_indexed() && !options.keep_indexed {
+        image = image.expand_color_map();
+    }
+    match options.format {
+        Some(target) if image.format.is_indexed() || image.format == target => (),
+        Some(target) if target.is_indexed() => return Err(TargaError::Unsupported("conversion to an indexed format")),
+        Some(target) => image = image.convert(target),
+        None => (),
+    }
+    image.set_origin(options.origin);
+
+    Ok(image)
+}
+
+// How the pixels of a particular file are stored.
+struct FileFormat<'a> {
+    format: PixelFormat,
+    alpha_bits: u8,
+    color_map: Option<&'a ColorMap>,
+    origin: Origin,
+}
+
+impl FileFormat<'_> {
+    fn image(&self, pixels: &[u8], width: u32, height: u32) -> TargaImage {
+        let bytes = match self.format {
+            PixelFormat::Rgb8 => pixels.chunks_exact(3).flat_map(| p | [p[2], p[1], p[0]]).collect(),
+            PixelFormat::Rgba5551 => pixels
+                .chunks_exact(2)
+                .flat_map(| p | {
+                    // TGA stores ARGB 1555 with the alpha bit meaningless unless declared
+                    let packed = u16::from_le_bytes([p[0], p[1]]);
+                    let alpha = self.alpha_bits == 0 || packed & 0x8000 != 0;
+
+                    ((packed & 0x7fff) << 1 | alpha as u16).to_le_bytes()
+                })
+                .collect(),
+            _ => pixels.to_vec(),
+        };
 
         TargaImage {
             bytes,
             width,
             height,
+            format: self.format,
+            color_map: self.color_map.cloned(),
+            origin: self.origin,
+            metadata: None,
+        }
+    }
+
+    // Postage stamps are never compressed and are always expanded to RGBA.
+    fn postage_stamp(&self, data: &[u8], offset: usize) -> Option<TargaImage> {
+        if offset == 0 {
+            return None;
+        }
+        let size = data.get(offset..(offset + 2))?;
+        let (width, height) = (size[0] as u32, size[1] as u32);
+        let length = self.format.pixel_size() * (width * height) as usize;
+        let pixels = data.get((offset + 2)..)?.get(..length)?;
+
+        (length != 0).then(|| self.image(pixels, width, height).convert(PixelFormat::Rgba8))
+    }
+}
+
+fn map_entry(entry: &[u8], alpha_bits: u8) -> [u8; 4] {
+    match *entry {
+        [lo, hi] => {
+            let packed = u16::from_le_bytes([lo, hi]);
+            let alpha = if alpha_bits == 0 || packed & 0x8000 != 0 { 255 } else { 0 };
+
+            [
+                format::expand_bits(packed >> 10, 5),
+                format::expand_bits(packed >> 5, 5),
+                format::expand_bits(packed, 5),
+                alpha,
+            ]
+        },
+        [b, g, r] => [r, g, b, 255],
+        [b, g, r, a] => [r, g, b, a],
+        _ => unreachable!(),
+    }
+}
+
+// Packets may span scanlines, as older writers do not split runs at row ends.
+fn decode_rle(data: &[u8], pixel_size: usize, count: usize) -> Result<Vec<u8>, TargaError> {
+    // a run packet expands to at most 128 pixels, so refuse to allocate for
+    // an image the remaining data could never describe
+    if data.len() < count.div_ceil(128) * (1 + pixel_size) {
+        return Err(TargaError::ShortPixelData);
+    }
+
+    let length = pixel_size * count;
+    let mut pixels = Vec::with_capacity(length);
+    let mut index = 0;
+
+    while pixels.len() < length {
+        let packet = *data.get(index).ok_or(TargaError::ShortPixelData)?;
+        let repeat = (packet & 0x7f) as usize + 1;
+        index += 1;
+
+        if packet & 0x80 != 0 {
+            let pixel = data.get(index..(index + pixel_size)).ok_or(TargaError::ShortPixelData)?;
+            index += pixel_size;
+
+            for _ in 0..repeat {
+                pixels.extend_from_slice(pixel);
+            }
+        } else {
+            let raw = data.get(index..(index + pixel_size * repeat)).ok_or(TargaError::ShortPixelData)?;
+            index += pixel_size * repeat;
+
+            pixels.extend_from_slice(raw);
         }
     }
+    pixels.truncate(length);
+
+    Ok(pixels)
 }
 
-const TGA_HDR: usize = 18;
-const TGA_WIDTH: usize = 12;
-const TGA_HEIGHT: usize = 14;
+#[derive(Clone, Copy, Debug)]
+pub struct WriteOptions {
+    /// Run-length encode each scanline.
+    pub rle: bool,
+    /// 32 for BGRA, 24 to drop the alpha channel, 16 for ARGB 1555, or 8 for
+    /// grayscale; 8-bit images with a colour map of up to 256 entries are
+    /// written colour-mapped instead.
+    pub depth: u8,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            rle: false,
+            depth: 32,
+        }
+    }
+}
 
-pub fn read_targa(path: &str) -> Result<TargaImage> {
-    const COMPONENTS: usize = 16;
+pub fn write_targa(path: &str, image: &TargaImage) -> Result<(), TargaError> {
+    write_targa_with(path, image, &WriteOptions::default())
+}
 
-    let mut file = File::open(path)?;
+pub fn write_targa_with(path: &str, image: &TargaImage, options: &WriteOptions) -> Result<(), TargaError> {
+    encode(BufWriter::new(File::create(path)?), image, options)
+}
 
-    let mut data = Vec::new();
-    let _read = file.read_to_end(&mut data)?;
+pub fn encode<W: Write>(mut writer: W, image: &TargaImage, options: &WriteOptions) -> Result<(), TargaError> {
+    let color_map = image.color_map.as_ref().filter(| map | {
+        options.depth == 8 && image.format == PixelFormat::Indexed8 && map.entries.len() <= 256
+    });
+    let format = match options.depth {
+        8 if color_map.is_some() => PixelFormat::Indexed8,
+        8 => PixelFormat::Gray8,
+        16 => PixelFormat::Rgba5551,
+        24 | 32 => PixelFormat::Rgba8,
+        depth => return Err(TargaError::UnsupportedPixelDepth(depth)),
+    };
+    let image = if image.format == format { Cow::Borrowed(image) } else { Cow::Owned(image.convert(format)) };
+    check_dimensions(&image, u16::MAX as u32)?;
+
+    let metadata = image.metadata.as_ref();
+    let image_id = metadata.map_or(&[][..], | metadata | &metadata.image_id);
+    let image_id = &image_id[..image_id.len().min(u8::MAX as usize)];
+    let image_type = match format {
+        PixelFormat::Indexed8 => COLOR_MAPPED,
+        PixelFormat::Gray8 => GRAYSCALE,
+        _ => TRUECOLOR,
+    };
+    let alpha_bits = match options.depth {
+        32 => 8,
+        16 => 1,
+        _ => 0,
+    };
+
+    let mut header = [0u8; TGA_HDR];
+    header[TGA_ID_LENGTH] = image_id.len() as u8;
+    header[TGA_TYPE] = if options.rle { image_type + 8 } else { image_type };
+    header[TGA_WIDTH..(TGA_WIDTH + 2)].copy_from_slice(&(image.width as u16).to_le_bytes());
+    header[TGA_HEIGHT..(TGA_HEIGHT + 2)].copy_from_slice(&(image.height as u16).to_le_bytes());
+    header[TGA_DEPTH] = options.depth;
+    header[TGA_DESCRIPTOR] = image.origin.descriptor_bits() | alpha_bits;
 
-    let components = data[COMPONENTS];
-    if components != 32 {
-        panic!("unexpected TGA format");
+    // colour maps are written as BGRA, or BGR when every entry is opaque
+    let mut map_data = Vec::new();
+    if let Some(map) = color_map {
+        let opaque = map.entries.iter().all(| entry | entry[3] == 255);
+        let entry_size = if opaque { 3 } else { 4 };
+        for &[r, g, b, a] in &map.entries {
+            map_data.extend_from_slice(&[b, g, r, a][..entry_size]);
+        }
+
+        header[TGA_MAP_TYPE] = 1;
+        header[TGA_MAP_FIRST..(TGA_MAP_FIRST + 2)].copy_from_slice(&map.first_entry.to_le_bytes());
+        header[TGA_MAP_LENGTH..(TGA_MAP_LENGTH + 2)].copy_from_slice(&(map.entries.len() as u16).to_le_bytes());
+        header[TGA_MAP_DEPTH] = 8 * entry_size as u8;
     }
-    let width = data[TGA_WIDTH + 1] as u32 * 256 + data[TGA_WIDTH] as u32;
-    let height = data[TGA_HEIGHT + 1] as u32 * 256 + data[TGA_HEIGHT] as u32;
-    let mut bytes = Vec::new();
-    for i in 0..(width * height) as usize {
-        let index = TGA_HDR + 4 * i;
 
-        bytes.push(data[index + 2]);
-        bytes.push(data[index + 1]);
-        bytes.push(data[index]);
-        bytes.push(data[index + 3]);
+    writer.write_all(&header)?;
+    writer.write_all(image_id)?;
+    writer.write_all(&map_data)?;
+
+    let pixel_size = header_pixel_size(options.depth);
+    let pixels = file_pixels(&image, options.depth);
+    let pixels = if options.rle {
+        pixels
+            .chunks_exact(pixel_size * image.width as usize)
+            .flat_map(| row | encode_rle(row, pixel_size))
+            .collect()
+    } else {
+        pixels
+    };
+    writer.write_all(&pixels)?;
+
+    if let Some(metadata) = metadata {
+        let mut offset = TGA_HDR + image_id.len() + map_data.len() + pixels.len();
+
+        // a colour-mapped stamp would need the image's palette, so none is written
+        let mut stamp_offset = 0;
+        if let Some(stamp) = metadata.postage_stamp.as_ref().filter(| _ | color_map.is_none()) {
+            let mut stamp = stamp.convert(format);
+            check_dimensions(&stamp, u8::MAX as u32)?;
+            stamp.set_origin(image.origin);
+            let stamp_pixels = file_pixels(&stamp, options.depth);
+
+            writer.write_all(&[stamp.width as u8, stamp.height as u8])?;
+            writer.write_all(&stamp_pixels)?;
+
+            stamp_offset = offset;
+            offset += 2 + stamp_pixels.len();
+        }
+
+        let extension_offset = u32::try_from(offset)
+            .map_err(| _ | TargaError::BadDimensions { width: image.width, height: image.height })?;
+        writer.write_all(&metadata::extension_area(metadata, stamp_offset as u32))?;
+        writer.write_all(&metadata::footer(extension_offset))?;
     }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn header_pixel_size(depth: u8) -> usize {
+    (depth as usize).div_ceil(8)
+}
 
-    Ok(TargaImage {
-        bytes,
-        width,
-        height,
-    })
+// Pixels of an image already in the format its depth calls for, in the byte
+// order of the file: BGRA or BGR, ARGB 1555, or grayscale levels and indices as they are.
+fn file_pixels(image: &TargaImage, depth: u8) -> Vec<u8> {
+    match image.format {
+        PixelFormat::Rgba8 => {
+            let pixel_size = header_pixel_size(depth);
+            let mut pixels = Vec::with_capacity(pixel_size * image.bytes.len() / 4);
+            for pixel in image.bytes.chunks_exact(4) {
+                pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]][..pixel_size]);
+            }
+            pixels
+        },
+        PixelFormat::Rgba5551 => image
+            .bytes
+            .chunks_exact(2)
+            .flat_map(| pixel | {
+                let packed = u16::from_le_bytes([pixel[0], pixel[1]]);
+                (packed >> 1 | (packed & 1) << 15).to_le_bytes()
+            })
+            .collect(),
+        _ => image.bytes.clone(),
+    }
 }
 
-pub fn write_targa(path: &str, mut image: TargaImage) -> Result<()> {
-    let mut header = [0u8, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 32, 0];
-    header[TGA_WIDTH] = (image.width % 256) as u8;
-    header[TGA_WIDTH + 1] = (image.width / 256) as u8;
-    header[TGA_HEIGHT] = (image.height % 256) as u8;
-    header[TGA_HEIGHT + 1] = (image.height / 256) as u8;
+// Packets never cross the end of `row`, as TGA 2.0 requires.
+fn encode_rle(row: &[u8], pixel_size: usize) -> Vec<u8> {
+    let pixels: Vec<&[u8]> = row.chunks_exact(pixel_size).collect();
+    let run_length = | start: usize | {
+        pixels[start..]
+            .iter()
+            .take(128)
+            .take_while(| &&pixel | pixel == pixels[start])
+            .count()
+    };
 
-    let mut file = File::create(path)?;
+    let mut encoded = Vec::with_capacity(row.len() + row.len() / 128 + 1);
+    let mut start = 0;
+    while start < pixels.len() {
+        let run = run_length(start);
+        if run > 1 {
+            encoded.push(0x80 | (run - 1) as u8);
+            encoded.extend_from_slice(pixels[start]);
+            start += run;
+            continue;
+        }
 
-    file.write_all(&header)?;
+        let mut end = start + 1;
+        while end < pixels.len() && end - start < 128 && run_length(end) < 2 {
+            end += 1;
+        }
+        encoded.push((end - start - 1) as u8);
+        for pixel in &pixels[start..end] {
+            encoded.extend_from_slice(pixel);
+        }
+        start = end;
+    }
 
-    for i in 0..(image.width * image.height) as usize {
-        let index = 4 * i;
+    encoded
+}
 
-        image.bytes.swap(index, index + 2);
+fn check_dimensions(image: &TargaImage, max: u32) -> Result<(), TargaError> {
+    let (width, height) = (image.width, image.height);
+    if width == 0 || height == 0 || width > max || height > max {
+        return Err(TargaError::BadDimensions { width, height });
+    }
+    if image.bytes.len() != image.pixel_size() * (width * height) as usize {
+        return Err(TargaError::ShortPixelData);
     }
-    file.write_all(&image.bytes)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // xorshift keeps the inputs reproducible without a dependency
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+
+        fn bytes(&mut self, length: usize) -> Vec<u8> {
+            (0..length).map(| _ | self.next() as u8).collect()
+        }
+    }
+
+    fn header(image_type: u8, depth: u8, width: u16, height: u16) -> Vec<u8> {
+        let mut header = vec![0u8; TGA_HDR];
+        header[TGA_TYPE] = image_type;
+        header[TGA_WIDTH..(TGA_WIDTH + 2)].copy_from_slice(&width.to_le_bytes());
+        header[TGA_HEIGHT..(TGA_HEIGHT + 2)].copy_from_slice(&height.to_le_bytes());
+        header[16] = depth;
+        header
+    }
+
+    fn samples(rng: &mut Rng) -> Vec<Vec<u8>> {
+        let mut samples = Vec::new();
+
+        for (image_type, depth) in [(2, 16), (2, 24), (2, 32), (3, 8), (3, 16)] {
+            let mut raw = header(image_type, depth, 5, 3);
+            raw.extend(rng.bytes(15 * depth as usize / 8));
+            samples.push(raw);
+
+            let mut rle = header(image_type + 8, depth, 5, 3);
+            rle.extend([0x84]);
+            rle.extend(rng.bytes(depth as usize / 8));
+            rle.extend([0x09]);
+            rle.extend(rng.bytes(10 * depth as usize / 8));
+            samples.push(rle);
+        }
+
+        for image_type in [COLOR_MAPPED, RLE_COLOR_MAPPED] {
+            let mut mapped = header(image_type, 8, 4, 4);
+            mapped[TGA_MAP_TYPE] = 1;
+            mapped[TGA_MAP_LENGTH] = 4;
+            mapped[TGA_MAP_DEPTH] = 24;
+            mapped.extend(rng.bytes(12));
+            if image_type == RLE_COLOR_MAPPED {
+                mapped.extend([0x8f, 2]);
+            } else {
+                mapped.extend((0..16).map(| i | i % 4));
+            }
+            samples.push(mapped);
+        }
+
+        samples
+    }
+
+    #[test]
+    fn samples_decode() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+
+        for sample in samples(&mut rng) {
+            let image = decode_targa(&sample, &ReadOptions::default()).unwrap();
+            assert_eq!(image.bytes.len(), 4 * (image.width * image.height) as usize);
+        }
+    }
+
+    #[test]
+    fn run_length_packets_expand_across_rows() {
+        // a run of four pixels spans both rows, then a raw packet gives the last two
+        let mut sample = header(RLE_TRUECOLOR, 24, 3, 2);
+        sample.extend([0x83, 1, 2, 3, 0x01, 4, 5, 6, 7, 8, 9]);
+        let image = decode_targa(&sample, &ReadOptions::default()).unwrap();
+        assert_eq!(
+            image.bytes.chunks(4).collect::<Vec<_>>(),
+            [[3, 2, 1, 255], [3, 2, 1, 255], [3, 2, 1, 255], [3, 2, 1, 255], [6, 5, 4, 255], [9, 8, 7, 255]]
+        );
+
+        let mut sample = header(RLE_GRAYSCALE, 8, 4, 1);
+        sample.extend([0x01, 10, 20, 0x81, 30]);
+        let image = decode_targa(&sample, &ReadOptions::default()).unwrap();
+        assert_eq!(image.bytes.chunks(4).map(| p | p[0]).collect::<Vec<_>>(), [10, 20, 30, 30]);
+
+        // a packet that overruns the image is cut short
+        let mut sample = header(RLE_GRAYSCALE, 8, 2, 1);
+        sample.extend([0x85, 40]);
+        assert_eq!(decode_targa(&sample, &ReadOptions::default()).unwrap().bytes.len(), 8);
+    }
+
+    #[test]
+    fn every_depth_expands_to_rgba() {
+        let rgba = | image_type, depth, descriptor, pixels: &[u8] | {
+            let mut sample = header(image_type, depth, 2, 1);
+            sample[TGA_DESCRIPTOR] = descriptor;
+            sample.extend(pixels);
+            decode_targa(&sample, &ReadOptions::default()).unwrap().bytes
+        };
+
+        // ARGB 1555, whose alpha bit only counts when the descriptor declares it
+        let (red, blue) = ([0x00, 0xfc], [0x1f, 0x00]);
+        assert_eq!(rgba(TRUECOLOR, 16, 1, &[red, blue].concat()), [255, 0, 0, 255, 0, 0, 255, 0]);
+        assert_eq!(rgba(TRUECOLOR, 15, 0, &[red, blue].concat()), [255, 0, 0, 255, 0, 0, 255, 255]);
+        assert_eq!(rgba(TRUECOLOR, 24, 0, &[1, 2, 3, 4, 5, 6]), [3, 2, 1, 255, 6, 5, 4, 255]);
+        assert_eq!(rgba(TRUECOLOR, 32, 8, &[1, 2, 3, 4, 5, 6, 7, 8]), [3, 2, 1, 4, 7, 6, 5, 8]);
+        assert_eq!(rgba(GRAYSCALE, 8, 0, &[9, 200]), [9, 9, 9, 255, 200, 200, 200, 255]);
+        assert_eq!(rgba(GRAYSCALE, 16, 8, &[9, 100, 200, 0]), [9, 9, 9, 100, 200, 200, 200, 0]);
+    }
+
+    #[test]
+    fn colour_maps_expand_or_keep_their_indices() {
+        let mut sample = header(COLOR_MAPPED, 8, 3, 1);
+        sample[TGA_MAP_TYPE] = 1;
+        sample[TGA_MAP_FIRST] = 2;
+        sample[TGA_MAP_LENGTH] = 2;
+        sample[TGA_MAP_DEPTH] = 24;
+        sample.extend([1, 2, 3, 4, 5, 6]);
+        // index 0 comes before the first entry
+        sample.extend([3, 2, 0]);
+
+        let image = decode_targa(&sample, &ReadOptions::default()).unwrap();
+        assert_eq!(image.bytes, [6, 5, 4, 255, 3, 2, 1, 255, 0, 0, 0, 0]);
+
+        let indexed = ReadOptions { keep_indexed: true, ..Default::default() };
+        let image = decode_targa(&sample, &indexed).unwrap();
+        assert_eq!((image.format, &image.bytes[..]), (PixelFormat::Indexed8, &[3, 2, 0][..]));
+        let color_map = image.color_map.unwrap();
+        assert_eq!((color_map.first_entry, color_map.entries), (2, vec![[3, 2, 1, 255], [6, 5, 4, 255]]));
+
+        // 16-bit indices into ARGB 1555 entries, with a declared alpha bit
+        let mut sample = header(COLOR_MAPPED, 16, 2, 1);
+        sample[TGA_MAP_TYPE] = 1;
+        sample[TGA_MAP_LENGTH] = 2;
+        sample[TGA_MAP_DEPTH] = 16;
+        sample[TGA_DESCRIPTOR] = 1;
+        sample.extend([0x00, 0x7c, 0xe0, 0x83]);
+        sample.extend([1, 0, 0, 0]);
+        let image = decode_targa(&sample, &ReadOptions::default()).unwrap();
+        assert_eq!(image.bytes, [0, 255, 0, 255, 255, 0, 0, 0]);
+    }
+
+    #[test]
+    fn truncated_inputs_are_errors() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+
+        for sample in samples(&mut rng) {
+            for length in 0..sample.len() {
+                assert!(decode_targa(&sample[..length], &ReadOptions::default()).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn mutated_inputs_do_not_panic() {
+        let mut rng = Rng(0xd1b54a32d192ed03);
+        let samples = samples(&mut rng);
+
+        for _ in 0..20_000 {
+            let mut sample = samples[rng.below(samples.len())].clone();
+            for _ in 0..(1 + rng.below(4)) {
+                let index = rng.below(sample.len());
+                sample[index] = rng.next() as u8;
+            }
+            let keep_indexed = rng.below(2) == 0;
+            let origin = [Origin::BottomLeft, Origin::TopRight][rng.below(2)];
+            let format = [
+                None,
+                Some(PixelFormat::Rgba8),
+                Some(PixelFormat::Gray8),
+                Some(PixelFormat::Rgb565),
+                Some(PixelFormat::Indexed8),
+                Some(PixelFormat::Indexed16),
+            ][rng.below(6)];
+
+            let _ = decode_targa(&sample, &ReadOptions { keep_indexed, origin, format });
+        }
+    }
+
+    #[test]
+    fn random_inputs_do_not_panic() {
+        let mut rng = Rng(0x853c49e6748fea9b);
+
+        for _ in 0..20_000 {
+            let length = rng.below(512);
+            let mut sample = rng.bytes(length);
+            // steer most inputs past the header checks
+            if length > TGA_TYPE && rng.below(4) != 0 {
+                sample[TGA_TYPE] = [1, 2, 3, 9, 10, 11][rng.below(6)];
+            }
+
+            let _ = decode_targa(&sample, &ReadOptions::default());
+        }
+    }
+
+    #[test]
+    fn rows_follow_the_requested_origin() {
+        let mut sample = header(GRAYSCALE, 8, 3, 2);
+        sample[TGA_DESCRIPTOR] = 0x20;
+        sample.extend([1, 2, 3, 4, 5, 6]);
+
+        let top_left = ReadOptions { origin: Origin::TopLeft, ..Default::default() };
+        let image = decode_targa(&sample, &top_left).unwrap();
+        assert_eq!(image.bytes.chunks(4).map(| p | p[0]).collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6]);
+
+        let image = decode_targa(&sample, &ReadOptions::default()).unwrap();
+        assert_eq!(image.origin, Origin::BottomLeft);
+        assert_eq!(image.bytes.chunks(4).map(| p | p[0]).collect::<Vec<_>>(), [4, 5, 6, 1, 2, 3]);
+
+        let top_right = ReadOptions { origin: Origin::TopRight, ..Default::default() };
+        let image = decode_targa(&sample, &top_right).unwrap();
+        assert_eq!(image.bytes.chunks(4).map(| p | p[0]).collect::<Vec<_>>(), [3, 2, 1, 6, 5, 4]);
+    }
+
+    #[test]
+    fn metadata_round_trips() {
+        let mut stamp = TargaImage::new(vec![10, 20, 30, 255, 40, 50, 60, 128], 2, 1);
+        stamp.origin = Origin::TopLeft;
+        let mut image = TargaImage::new((0..64).collect(), 4, 4);
+        image.metadata = Some(TargaMetadata {
+            image_id: b"screenshot".to_vec(),
+            author: "Demos".to_string(),
+            comments: "first line\n\nthird line".to_string(),
+            timestamp: Some(Timestamp::from(std::time::UNIX_EPOCH + std::time::Duration::from_secs(951_827_696))),
+            software_id: "glium_app".to_string(),
+            software_version: Some((10, 'a')),
+            key_color: [1, 2, 3, 4],
+            gamma: (22, 10),
+            postage_stamp: Some(Box::new(stamp.clone())),
+            attribute_type: AttributeType::Alpha,
+            ..Default::default()
+        });
+
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &image, &WriteOptions::default()).unwrap();
+        let read = decode(&encoded[..], &ReadOptions::default()).unwrap();
+
+        stamp.set_origin(Origin::BottomLeft);
+        image.metadata.as_mut().unwrap().postage_stamp = Some(Box::new(stamp));
+        assert_eq!(read, image);
+
+        let timestamp = read.metadata.unwrap().timestamp.unwrap();
+        assert_eq!(
+            timestamp,
+            Timestamp { year: 2000, month: 2, day: 29, hour: 12, minute: 34, second: 56 }
+        );
+    }
+
+    #[test]
+    fn probe_stops_at_the_header() {
+        let mut sample = header(RLE_GRAYSCALE, 16, 300, 2);
+        sample[TGA_DESCRIPTOR] = 0x28;
+        let mut reader = &sample[..];
+
+        let header = probe(&mut reader).unwrap();
+        assert_eq!((header.width, header.height), (300, 2));
+        assert_eq!((header.image_type, header.rle), (ImageType::Grayscale, true));
+        assert_eq!((header.origin, header.alpha_bits), (Origin::TopLeft, 8));
+        assert!(matches!(probe(&sample[..10]), Err(TargaError::TruncatedHeader)));
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let mut rng = Rng(0xa0761d6478bd642f);
+        let palette = [[0, 0, 0, 255], [255, 255, 255, 255], [200, 16, 32, 128]];
+        let bytes = (0..(300 * 7))
+            .flat_map(| i | if i % 97 < 40 { palette[i % 3] } else { palette[rng.below(3)] })
+            .collect();
+        let image = TargaImage::new(bytes, 300, 7);
+        let opaque = TargaImage {
+            bytes: image.bytes.chunks(4).flat_map(| p | [p[0], p[1], p[2], 255]).collect(),
+            ..image.clone()
+        };
+
+        for (rle, depth, expected) in [(false, 32, &image), (true, 32, &image), (true, 24, &opaque)] {
+            let mut encoded = Vec::new();
+            encode(&mut encoded, &image, &WriteOptions { rle, depth }).unwrap();
+
+            if rle {
+                assert!(encoded.len() < TGA_HDR + 300 * 7 * depth as usize / 8);
+            }
+            assert_eq!(&decode(&encoded[..], &ReadOptions::default()).unwrap(), expected);
+        }
+
+        let native = ReadOptions { keep_indexed: true, format: None, ..Default::default() };
+        let indexed = TargaImage {
+            bytes: image.bytes.chunks(4).map(| p | palette.iter().position(| entry | entry == p).unwrap() as u8).collect(),
+            format: PixelFormat::Indexed8,
+            color_map: Some(ColorMap { first_entry: 0, entries: palette.to_vec() }),
+            ..image.clone()
+        };
+        for (source, depth) in [
+            (image.convert(PixelFormat::Gray8), 8),
+            (image.convert(PixelFormat::Rgba5551), 16),
+            (indexed.clone(), 8),
+        ] {
+            for rle in [false, true] {
+                let mut encoded = Vec::new();
+                encode(&mut encoded, &source, &WriteOptions { rle, depth }).unwrap();
+                assert_eq!(decode(&encoded[..], &native).unwrap(), source);
+            }
+        }
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &indexed, &WriteOptions { rle: false, depth: 8 }).unwrap();
+        assert_eq!(probe(&encoded[..]).unwrap().image_type, ImageType::ColorMapped);
+        assert!(matches!(
+            encode(Vec::new(), &image, &WriteOptions { rle: false, depth: 15 }),
+            Err(TargaError::UnsupportedPixelDepth(15))
+        ));
+    }
+
+    #[test]
+    fn native_formats_convert_losslessly() {
+        let mut rng = Rng(0xe7037ed1a0b428db);
+        let formats = [
+            PixelFormat::Gray8,
+            PixelFormat::GrayA8,
+            PixelFormat::Rgb8,
+            PixelFormat::Rgba8,
+            PixelFormat::Bgra8,
+            PixelFormat::Rgb565,
+            PixelFormat::Rgba5551,
+        ];
+        let native = ReadOptions { format: None, ..Default::default() };
+
+        for (sample, expected) in samples(&mut rng).iter().zip([
+            PixelFormat::Rgba5551,
+            PixelFormat::Rgba5551,
+            PixelFormat::Rgb8,
+            PixelFormat::Rgb8,
+            PixelFormat::Bgra8,
+            PixelFormat::Bgra8,
+            PixelFormat::Gray8,
+            PixelFormat::Gray8,
+            PixelFormat::GrayA8,
+            PixelFormat::GrayA8,
+            PixelFormat::Rgba8,
+            PixelFormat::Rgba8,
+        ]) {
+            let image = decode_targa(sample, &native).unwrap();
+            assert_eq!(image.format, expected);
+
+            for format in formats {
+                let converted = image.convert(format);
+                assert_eq!(converted.bytes.len(), format.pixel_size() * (image.width * image.height) as usize);
+            }
+
+            let wider: &[PixelFormat] = match image.format {
+                PixelFormat::Gray8 => &[PixelFormat::GrayA8, PixelFormat::Rgb8, PixelFormat::Rgba8, PixelFormat::Bgra8],
+                PixelFormat::Rgba8 => &[PixelFormat::Bgra8],
+                PixelFormat::Bgra8 => &[PixelFormat::Rgba8],
+                _ => &[PixelFormat::Rgba8, PixelFormat::Bgra8],
+            };
+            for &format in wider {
+                assert_eq!(image.convert(format).convert(image.format), image, "{:?} via {format:?}", image.format);
+            }
+        }
+    }
+
+    #[test]
+    fn huge_dimensions_are_refused_before_allocating() {
+        let mut sample = header(RLE_TRUECOLOR, 32, u16::MAX, u16::MAX);
+        sample.extend([0xff, 1, 2, 3, 4]);
+
+        assert!(matches!(
+            decode_targa(&sample, &ReadOptions::default()),
+            Err(TargaError::ShortPixelData)
+        ));
+    }
+
+    #[test]
+    fn errors_name_the_problem() {
+        let options = ReadOptions::default();
+
+        assert!(matches!(decode_targa(&[0; 17], &options), Err(TargaError::TruncatedHeader)));
+        assert!(matches!(
+            decode_targa(&header(4, 32, 1, 1), &options),
+            Err(TargaError::UnsupportedImageType(4))
+        ));
+        assert!(matches!(
+            decode_targa(&header(TRUECOLOR, 12, 1, 1), &options),
+            Err(TargaError::UnsupportedPixelDepth(12))
+        ));
+        assert!(matches!(
+            decode_targa(&header(TRUECOLOR, 32, 0, 1), &options),
+            Err(TargaError::BadDimensions { width: 0, height: 1 })
+        ));
+        assert!(matches!(
+            decode_targa(&header(TRUECOLOR, 32, 2, 1), &options),
+            Err(TargaError::ShortPixelData)
+        ));
+
+        let mut sample = header(GRAYSCALE, 8, 1, 1);
+        sample.push(7);
+        let indexed = ReadOptions { format: Some(PixelFormat::Indexed8), ..options };
+        assert!(matches!(decode_targa(&sample, &indexed), Err(TargaError::Unsupported(_))));
+    }
+
+    #[test]
+    fn every_format_is_detected_and_survives_mutation() {
+        let mut rng = Rng(0xa0761d6478bd642f);
+        let mut image = TargaImage::new(rng.bytes(4 * 9 * 7), 9, 7);
+        image.origin = Origin::TopLeft;
+
+        let mut samples = Vec::new();
+        for format in [ImageFormat::Targa, ImageFormat::Png, ImageFormat::Bmp, ImageFormat::Pnm] {
+            let mut encoded = Vec::new();
+            encode_image(&mut encoded, &image, format).unwrap();
+            assert_eq!(ImageFormat::detect(&encoded), format);
+
+            let decoded = decode_image(&encoded[..], &ReadOptions { origin: Origin::TopLeft, ..Default::default() });
+            if format == ImageFormat::Pnm {
+                assert_eq!(decoded.unwrap(), image.convert(PixelFormat::Rgb8).convert(PixelFormat::Rgba8));
+            } else {
+                assert_eq!(decoded.unwrap(), image);
+            }
+            samples.push(encoded);
+        }
+        assert_eq!(ImageFormat::from_path("res/font.PNG"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path("screenshot"), None);
+
+        for _ in 0..5_000 {
+            let mut sample = samples[rng.below(samples.len())].clone();
+            if rng.below(4) == 0 {
+                sample.truncate(rng.below(sample.len()));
+            }
+            for _ in 0..(1 + rng.below(4)) {
+                // spare the signatures, so the mutations reach the decoders
+                if sample.len() > 2 {
+                    let index = 2 + rng.below(sample.len() - 2);
+                    sample[index] = rng.next() as u8;
+                }
+            }
+
+            let _ = decode_image(&sample[..], &ReadOptions::default());
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::TargaImage;
+
+/// Everything a TGA file says about its image besides the pixels: the
+/// image ID field and the TGA 2.0 extension area.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TargaMetadata {
+    pub image_id: Vec<u8>,
+    pub author: String,
+    /// Up to four lines of 80 characters, separated by `'\n'`.
+    pub comments: String,
+    pub timestamp: Option<Timestamp>,
+    pub job_name: String,
+    /// Hours, minutes and seconds spent on the job.
+    pub job_time: (u16, u16, u16),
+    pub software_id: String,
+    /// Version times 100 and a letter, so 4.17b is `(417, 'b')`.
+    pub software_version: Option<(u16, char)>,
+    /// RGBA colour of the background, or of transparent pixels.
+    pub key_color: [u8; 4],
+    /// Numerator and denominator; a zero denominator means unspecified.
+    pub pixel_aspect: (u16, u16),
+    /// Numerator and denominator; a zero denominator means unspecified.
+    pub gamma: (u16, u16),
+    /// Thumbnail of at most 255x255 pixels.
+    pub postage_stamp: Option<Box<TargaImage>>,
+    pub attribute_type: AttributeType,
+}
+
+/// How the alpha channel, if any, should be interpreted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttributeType {
+    #[default]
+    NoAlpha,
+    UndefinedIgnore,
+    UndefinedRetain,
+    Alpha,
+    PremultipliedAlpha,
+    Reserved(u8),
+}
+
+impl From<u8> for AttributeType {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => AttributeType::NoAlpha,
+            1 => AttributeType::UndefinedIgnore,
+            2 => AttributeType::UndefinedRetain,
+            3 => AttributeType::Alpha,
+            4 => AttributeType::PremultipliedAlpha,
+            value => AttributeType::Reserved(value),
+        }
+    }
+}
+
+impl From<AttributeType> for u8 {
+    fn from(value: AttributeType) -> Self {
+        match value {
+            AttributeType::NoAlpha => 0,
+            AttributeType::UndefinedIgnore => 1,
+            AttributeType::UndefinedRetain => 2,
+            AttributeType::Alpha => 3,
+            AttributeType::PremultipliedAlpha => 4,
+            AttributeType::Reserved(value) => value,
+        }
+    }
+}
+
+/// Calendar date and time of day, in UTC when produced by `now`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u16,
+    pub day: u16,
+    pub hour: u16,
+    pub minute: u16,
+    pub second: u16,
+}
+
+impl Timestamp {
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        let seconds = time.duration_since(UNIX_EPOCH).map(| d | d.as_secs()).unwrap_or(0) as i64;
+        let days = seconds.div_euclid(86_400);
+        let time_of_day = seconds.rem_euclid(86_400);
+
+        // civil-from-days, counting eras of 400 years from 0000-03-01
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z - era * 146_097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Timestamp {
+            year: year as u16,
+            month: month as u16,
+            day: day as u16,
+            hour: (time_of_day / 3600) as u16,
+            minute: (time_of_day / 60 % 60) as u16,
+            second: (time_of_day % 60) as u16,
+        }
+    }
+}
+
+pub(crate) const FOOTER_SIZE: usize = 26;
+const SIGNATURE: &[u8; 18] = b"TRUEVISION-XFILE.\0";
+
+const EXT_SIZE: usize = 495;
+const EXT_AUTHOR: usize = 2;
+const EXT_COMMENTS: usize = 43;
+const EXT_TIMESTAMP: usize = 367;
+const EXT_JOB_NAME: usize = 379;
+const EXT_JOB_TIME: usize = 420;
+const EXT_SOFTWARE_ID: usize = 426;
+const EXT_SOFTWARE_VERSION: usize = 467;
+const EXT_KEY_COLOR: usize = 470;
+const EXT_PIXEL_ASPECT: usize = 474;
+const EXT_GAMMA: usize = 478;
+const EXT_POSTAGE_STAMP: usize = 486;
+const EXT_ATTRIBUTE_TYPE: usize = 494;
+
+const NAME_LENGTH: usize = 41;
+const COMMENT_LINE: usize = 81;
+
+/// Offset of the extension area named by a TGA 2.0 footer, if the file has one.
+pub(crate) fn extension_offset(data: &[u8]) -> Option<usize> {
+    let footer = data.get(data.len().checked_sub(FOOTER_SIZE)?..)?;
+    if &footer[8..] != SIGNATURE {
+        return None;
+    }
+
+    let offset = u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]]) as usize;
+    (offset != 0).then_some(offset)
+}
+
+/// Parses the extension area, leaving the postage stamp for the caller to
+/// decode from the returned offset. Malformed areas are treated as absent.
+pub(crate) fn parse_extension(data: &[u8], offset: usize) -> Option<(TargaMetadata, usize)> {
+    let area = data.get(offset..)?.get(..EXT_SIZE)?;
+    if (u16_at(area, 0) as usize) < EXT_SIZE {
+        return None;
+    }
+
+    let timestamp = Timestamp {
+        month: u16_at(area, EXT_TIMESTAMP),
+        day: u16_at(area, EXT_TIMESTAMP + 2),
+        year: u16_at(area, EXT_TIMESTAMP + 4),
+        hour: u16_at(area, EXT_TIMESTAMP + 6),
+        minute: u16_at(area, EXT_TIMESTAMP + 8),
+        second: u16_at(area, EXT_TIMESTAMP + 10),
+    };
+    let version = u16_at(area, EXT_SOFTWARE_VERSION);
+    let letter = area[EXT_SOFTWARE_VERSION + 2];
+    let [b, g, r, a] = [
+        area[EXT_KEY_COLOR],
+        area[EXT_KEY_COLOR + 1],
+        area[EXT_KEY_COLOR + 2],
+        area[EXT_KEY_COLOR + 3],
+    ];
+    let comments: Vec<String> = area[EXT_COMMENTS..(EXT_COMMENTS + 4 * COMMENT_LINE)]
+        .chunks_exact(COMMENT_LINE)
+        .map(read_string)
+        .collect();
+
+    let metadata = TargaMetadata {
+        image_id: Vec::new(),
+        author: read_string(&area[EXT_AUTHOR..(EXT_AUTHOR + NAME_LENGTH)]),
+        comments: comments.join("\n").trim_end_matches('\n').to_string(),
+        timestamp: (timestamp != Timestamp::default()).then_some(timestamp),
+        job_name: read_string(&area[EXT_JOB_NAME..(EXT_JOB_NAME + NAME_LENGTH)]),
+        job_time: (
+            u16_at(area, EXT_JOB_TIME),
+            u16_at(area, EXT_JOB_TIME + 2),
+            u16_at(area, EXT_JOB_TIME + 4),
+        ),
+        software_id: read_string(&area[EXT_SOFTWARE_ID..(EXT_SOFTWARE_ID + NAME_LENGTH)]),
+        software_version: (version != 0 || letter > b' ').then_some((version, letter as char)),
+        key_color: [r, g, b, a],
+        pixel_aspect: (u16_at(area, EXT_PIXEL_ASPECT), u16_at(area, EXT_PIXEL_ASPECT + 2)),
+        gamma: (u16_at(area, EXT_GAMMA), u16_at(area, EXT_GAMMA + 2)),
+        postage_stamp: None,
+        attribute_type: area[EXT_ATTRIBUTE_TYPE].into(),
+    };
+    let postage_stamp = u32::from_le_bytes([
+        area[EXT_POSTAGE_STAMP],
+        area[EXT_POSTAGE_STAMP + 1],
+        area[EXT_POSTAGE_STAMP + 2],
+        area[EXT_POSTAGE_STAMP + 3],
+    ]) as usize;
+
+    Some((metadata, postage_stamp))
+}
+
+pub(crate) fn extension_area(metadata: &TargaMetadata, postage_stamp_offset: u32) -> Vec<u8> {
+    let mut area = vec![0u8; EXT_SIZE];
+    area[..2].copy_from_slice(&(EXT_SIZE as u16).to_le_bytes());
+
+    write_string(&mut area[EXT_AUTHOR..(EXT_AUTHOR + NAME_LENGTH)], &metadata.author);
+    for (line, text) in area[EXT_COMMENTS..(EXT_COMMENTS + 4 * COMMENT_LINE)]
+        .chunks_exact_mut(COMMENT_LINE)
+        .zip(metadata.comments.lines())
+    {
+        write_string(line, text);
+    }
+    if let Some(timestamp) = metadata.timestamp {
+        let fields = [
+            timestamp.month,
+            timestamp.day,
+            timestamp.year,
+            timestamp.hour,
+            timestamp.minute,
+            timestamp.second,
+        ];
+        write_u16s(&mut area[EXT_TIMESTAMP..], &fields);
+    }
+    write_string(&mut area[EXT_JOB_NAME..(EXT_JOB_NAME + NAME_LENGTH)], &metadata.job_name);
+    let (hours, minutes, seconds) = metadata.job_time;
+    write_u16s(&mut area[EXT_JOB_TIME..], &[hours, minutes, seconds]);
+    write_string(&mut area[EXT_SOFTWARE_ID..(EXT_SOFTWARE_ID + NAME_LENGTH)], &metadata.software_id);
+    match metadata.software_version {
+        Some((version, letter)) => {
+            write_u16s(&mut area[EXT_SOFTWARE_VERSION..], &[version]);
+            area[EXT_SOFTWARE_VERSION + 2] = if letter.is_ascii() { letter as u8 } else { b' ' };
+        },
+        None => area[EXT_SOFTWARE_VERSION + 2] = b' ',
+    }
+    let [r, g, b, a] = metadata.key_color;
+    area[EXT_KEY_COLOR..(EXT_KEY_COLOR + 4)].copy_from_slice(&[b, g, r, a]);
+    write_u16s(&mut area[EXT_PIXEL_ASPECT..], &[metadata.pixel_aspect.0, metadata.pixel_aspect.1]);
+    write_u16s(&mut area[EXT_GAMMA..], &[metadata.gamma.0, metadata.gamma.1]);
+    area[EXT_POSTAGE_STAMP..(EXT_POSTAGE_STAMP + 4)].copy_from_slice(&postage_stamp_offset.to_le_bytes());
+    area[EXT_ATTRIBUTE_TYPE] = metadata.attribute_type.into();
+
+    area
+}
+
+pub(crate) fn footer(extension_offset: u32) -> [u8; FOOTER_SIZE] {
+    let mut footer = [0u8; FOOTER_SIZE];
+    footer[..4].copy_from_slice(&extension_offset.to_le_bytes());
+    footer[8..].copy_from_slice(SIGNATURE);
+    footer
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn write_u16s(data: &mut [u8], values: &[u16]) {
+    for (bytes, value) in data.chunks_exact_mut(2).zip(values) {
+        bytes.copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+// Fields are NUL-terminated ASCII, though some writers pad with spaces.
+fn read_string(field: &[u8]) -> String {
+    let end = field.iter().position(| &b | b == 0).unwrap_or(field.len());
+
+    String::from_utf8_lossy(&field[..end]).trim_end().to_string()
+}
+
+fn write_string(field: &mut [u8], text: &str) {
+    let room = field.len() - 1;
+
+    for (byte, ch) in field[..room].iter_mut().zip(text.chars().filter(char::is_ascii)) {
+        *byte = ch as u8;
+    }
+}
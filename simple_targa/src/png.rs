@@ -0,0 +1,546 @@
+//! PNG images of every colour type and bit depth, interlaced or not.
+//! 16-bit samples are narrowed to 8 bits, as `TargaImage` has no wider formats.
+
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use crate::{zlib, ColorMap, Origin, PixelFormat, ReadOptions, TargaError, TargaImage};
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+const GRAY: u8 = 0;
+const RGB: u8 = 2;
+const PALETTE: u8 = 3;
+const GRAY_ALPHA: u8 = 4;
+const RGBA: u8 = 6;
+
+// x and y of the first pixel and the steps between pixels of each Adam7 pass
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+pub fn decode<R: Read>(mut reader: R, options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    let mut data = Vec::new();
+    let _read = reader.read_to_end(&mut data)?;
+
+    decode_png(&data, options)
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(chunk: &[u8]) -> Result<Self, TargaError> {
+        if chunk.len() != 13 {
+            return Err(TargaError::InvalidData("bad PNG header length"));
+        }
+        let header = Header {
+            width: u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+            height: u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+            bit_depth: chunk[8],
+            color_type: chunk[9],
+            interlaced: chunk[12] == 1,
+        };
+
+        match (header.color_type, header.bit_depth) {
+            (GRAY, 1 | 2 | 4 | 8 | 16) | (PALETTE, 1 | 2 | 4 | 8) | (RGB | GRAY_ALPHA | RGBA, 8 | 16) => (),
+            (GRAY | PALETTE | RGB | GRAY_ALPHA | RGBA, depth) => return Err(TargaError::UnsupportedPixelDepth(depth)),
+            _ => return Err(TargaError::InvalidData("bad PNG colour type")),
+        }
+        if chunk[10] != 0 || chunk[11] != 0 || chunk[12] > 1 {
+            return Err(TargaError::InvalidData("bad PNG compression, filter or interlace method"));
+        }
+
+        Ok(header)
+    }
+
+    fn channels(&self) -> usize {
+        match self.color_type {
+            GRAY | PALETTE => 1,
+            GRAY_ALPHA => 2,
+            RGB => 3,
+            _ => 4,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    fn row_size(&self, width: usize) -> usize {
+        (width * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    // Width and height of each pass that has any pixels.
+    fn passes(&self) -> Vec<(usize, usize, usize, usize, usize, usize)> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let passes = if self.interlaced { &ADAM7[..] } else { &[(0, 0, 1, 1)] };
+
+        passes
+            .iter()
+            .map(| &(x, y, dx, dy) | (x, y, dx, dy, (width + dx - 1 - x) / dx, (height + dy - 1 - y) / dy))
+            .filter(| pass | pass.4 != 0 && pass.5 != 0)
+            .collect()
+    }
+}
+
+pub(crate) fn decode_png(data: &[u8], options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    if !data.starts_with(SIGNATURE) {
+        return Err(TargaError::InvalidData("missing PNG signature"));
+    }
+
+    let mut header = None;
+    let mut palette = None;
+    let mut transparency = None;
+    let mut compressed = Vec::new();
+    let mut offset = SIGNATURE.len();
+    loop {
+        let prefix = data.get(offset..(offset + 8)).ok_or(TargaError::InvalidData("truncated PNG chunk"))?;
+        let length = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+        let kind = [prefix[4], prefix[5], prefix[6], prefix[7]];
+        let chunk = data
+            .get((offset + 4)..)
+            .and_then(| data | data.get(..(length + 8)))
+            .ok_or(TargaError::InvalidData("truncated PNG chunk"))?;
+        let (checked, crc) = chunk.split_at(length + 4);
+        if u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]) != crc32(checked) {
+            return Err(TargaError::InvalidData("PNG chunk checksum mismatch"));
+        }
+        let body = &checked[4..];
+        offset += length + 12;
+
+        match &kind {
+            b"IHDR" => header = Some(Header::parse(body)?),
+            b"PLTE" => palette = Some(body),
+            b"tRNS" => transparency = Some(body),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            // ancillary chunks have a lowercase first letter and may be skipped
+            _ if kind[0].is_ascii_lowercase() => (),
+            _ => return Err(TargaError::Unsupported("unknown critical PNG chunk")),
+        }
+    }
+    let header = header.ok_or(TargaError::InvalidData("missing PNG header"))?;
+    let (width, height) = (header.width, header.height);
+    if width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(TargaError::BadDimensions { width, height });
+    }
+    let count = crate::pixel_count(width, height)?;
+
+    let passes = header.passes();
+    let length = passes
+        .iter()
+        .try_fold(0usize, | length, pass | {
+            let row = pass.4.checked_mul(header.bits_per_pixel())?.div_ceil(8).checked_add(1)?;
+            length.checked_add(row.checked_mul(pass.5)?)
+        })
+        .ok_or(TargaError::BadDimensions { width, height })?;
+    let filtered = zlib::decompress(&compressed, length)?;
+    if filtered.len() != length {
+        return Err(TargaError::ShortPixelData);
+    }
+
+    let color_map = match header.color_type {
+        PALETTE => {
+            let palette = palette.ok_or(TargaError::InvalidData("missing PNG palette"))?;
+            if palette.len() % 3 != 0 || palette.len() > 3 * 256 {
+                return Err(TargaError::InvalidData("bad PNG palette length"));
+            }
+            let alpha = transparency.unwrap_or(&[]);
+            let entries = palette
+                .chunks_exact(3)
+                .enumerate()
+                .map(| (i, rgb) | [rgb[0], rgb[1], rgb[2], *alpha.get(i).unwrap_or(&255)])
+                .collect();
+
+            Some(ColorMap { first_entry: 0, entries })
+        },
+        _ => None,
+    };
+    // grayscale and RGB images may name one colour as transparent
+    let key = match (header.color_type, transparency) {
+        (GRAY, Some(&[hi, lo, ..])) => Some(vec![u16::from_be_bytes([hi, lo])]),
+        (RGB, Some(&[r0, r1, g0, g1, b0, b1, ..])) => Some(vec![
+            u16::from_be_bytes([r0, r1]),
+            u16::from_be_bytes([g0, g1]),
+            u16::from_be_bytes([b0, b1]),
+        ]),
+        _ => None,
+    };
+    let format = match header.color_type {
+        GRAY if key.is_some() => PixelFormat::GrayA8,
+        GRAY => PixelFormat::Gray8,
+        RGB if key.is_some() => PixelFormat::Rgba8,
+        RGB => PixelFormat::Rgb8,
+        PALETTE => PixelFormat::Indexed8,
+        GRAY_ALPHA => PixelFormat::GrayA8,
+        _ => PixelFormat::Rgba8,
+    };
+
+    let pixel_size = format.pixel_size();
+    let stride = pixel_size * width as usize;
+    let mut bytes = vec![0u8; pixel_size * count];
+    let mut filtered = &filtered[..];
+    for (x0, y0, dx, dy, pass_width, pass_height) in passes {
+        let row_size = header.row_size(pass_width);
+        let mut previous = vec![0u8; row_size];
+        let mut row = vec![0u8; row_size];
+
+        for y in 0..pass_height {
+            let (filter, rest) = filtered.split_at(1);
+            row.copy_from_slice(&rest[..row_size]);
+            filtered = &rest[row_size..];
+            unfilter(filter[0], &mut row, &previous, header.bits_per_pixel().div_ceil(8))?;
+
+            let line = (y0 + y * dy) * stride;
+            for x in 0..pass_width {
+                let offset = line + (x0 + x * dx) * pixel_size;
+                put_pixel(&header, &row, x, key.as_deref(), &mut bytes[offset..(offset + pixel_size)]);
+            }
+            std::mem::swap(&mut row, &mut previous);
+        }
+    }
+
+    let image = TargaImage {
+        bytes,
+        width,
+        height,
+        format,
+        color_map,
+        origin: Origin::TopLeft,
+        metadata: None,
+    };
+
+    crate::apply_options(image, options)
+}
+
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], pixel_size: usize) -> Result<(), TargaError> {
+    match filter {
+        0 => (),
+        1 => {
+            for i in pixel_size..row.len() {
+                row[i] = row[i].wrapping_add(row[i - pixel_size]);
+            }
+        },
+        2 => {
+            for (byte, &above) in row.iter_mut().zip(previous) {
+                *byte = byte.wrapping_add(above);
+            }
+        },
+        3 => {
+            for i in 0..row.len() {
+                let left = if i >= pixel_size { row[i - pixel_size] } else { 0 };
+                row[i] = row[i].wrapping_add(((left as u16 + previous[i] as u16) / 2) as u8);
+            }
+        },
+        4 => {
+            for i in 0..row.len() {
+                let (left, upper_left) = if i >= pixel_size {
+                    (row[i - pixel_size], previous[i - pixel_size])
+                } else {
+                    (0, 0)
+                };
+                row[i] = row[i].wrapping_add(paeth(left, previous[i], upper_left));
+            }
+        },
+        _ => return Err(TargaError::InvalidData("bad PNG filter type")),
+    }
+
+    Ok(())
+}
+
+fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+    let estimate = left as i16 + above as i16 - upper_left as i16;
+    let (a, b, c) = (
+        (estimate - left as i16).abs(),
+        (estimate - above as i16).abs(),
+        (estimate - upper_left as i16).abs(),
+    );
+
+    if a <= b && a <= c {
+        left
+    } else if b <= c {
+        above
+    } else {
+        upper_left
+    }
+}
+
+// Writes pixel `x` of an unfiltered row in the image's native format.
+fn put_pixel(header: &Header, row: &[u8], x: usize, key: Option<&[u16]>, pixel: &mut [u8]) {
+    let depth = header.bit_depth as usize;
+    let channels = header.channels();
+    let max = (1u32 << depth) - 1;
+    let sample = | channel: usize | -> u16 {
+        match depth {
+            16 => {
+                let i = 2 * (x * channels + channel);
+                u16::from_be_bytes([row[i], row[i + 1]])
+            },
+            8 => row[x * channels + channel] as u16,
+            _ => {
+                let bit = x * depth;
+                (row[bit / 8] >> (8 - depth - bit % 8)) as u16 & max as u16
+            },
+        }
+    };
+    let narrow = | value: u16 | match depth {
+        16 => (value >> 8) as u8,
+        8 => value as u8,
+        _ => (value as u32 * 255 / max) as u8,
+    };
+
+    if header.color_type == PALETTE {
+        pixel[0] = sample(0) as u8;
+        return;
+    }
+    let samples: Vec<u16> = (0..channels).map(sample).collect();
+    for (byte, &value) in pixel.iter_mut().zip(&samples) {
+        *byte = narrow(value);
+    }
+    if let Some(key) = key {
+        pixel[channels] = if samples == key { 0 } else { 255 };
+    }
+}
+
+/// Encodes `image` as a non-interlaced PNG with 8-bit samples, keeping
+/// grayscale and colour-mapped images in their compact forms.
+pub fn encode<W: Write>(mut writer: W, image: &TargaImage) -> Result<(), TargaError> {
+    let (width, height) = (image.width, image.height);
+    if width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(TargaError::BadDimensions { width, height });
+    }
+    let count = crate::pixel_count(width, height)?;
+    if image.bytes.len() != image.pixel_size() * count {
+        return Err(TargaError::ShortPixelData);
+    }
+
+    let palette = image.color_map.as_ref().filter(| map | {
+        image.format == PixelFormat::Indexed8 && map.first_entry == 0 && map.entries.len() <= 256
+    });
+    let mut image = match image.format {
+        PixelFormat::Gray8 | PixelFormat::GrayA8 | PixelFormat::Rgb8 | PixelFormat::Rgba8 => Cow::Borrowed(image),
+        PixelFormat::Indexed8 if palette.is_some() => Cow::Borrowed(image),
+        PixelFormat::Rgb565 => Cow::Owned(image.convert(PixelFormat::Rgb8)),
+        PixelFormat::Indexed8 | PixelFormat::Indexed16 => Cow::Owned(image.expand_color_map()),
+        _ => Cow::Owned(image.convert(PixelFormat::Rgba8)),
+    };
+    if image.origin != Origin::TopLeft {
+        image.to_mut().set_origin(Origin::TopLeft);
+    }
+    let color_type = match image.format {
+        PixelFormat::Gray8 => GRAY,
+        PixelFormat::GrayA8 => GRAY_ALPHA,
+        PixelFormat::Rgb8 => RGB,
+        PixelFormat::Indexed8 => PALETTE,
+        _ => RGBA,
+    };
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+    writer.write_all(SIGNATURE)?;
+    write_chunk(&mut writer, b"IHDR", &header)?;
+    if let Some(palette) = palette {
+        let rgb: Vec<u8> = palette.entries.iter().flat_map(| entry | [entry[0], entry[1], entry[2]]).collect();
+        write_chunk(&mut writer, b"PLTE", &rgb)?;
+
+        let alpha: Vec<u8> = palette.entries.iter().map(| entry | entry[3]).collect();
+        if let Some(last) = alpha.iter().rposition(| &a | a != 255) {
+            write_chunk(&mut writer, b"tRNS", &alpha[..=last])?;
+        }
+    }
+
+    let pixel_size = image.pixel_size();
+    let stride = pixel_size * width as usize;
+    let mut filtered = Vec::with_capacity((stride + 1) * height as usize);
+    let mut previous = vec![0u8; stride];
+    for row in image.bytes.chunks_exact(stride) {
+        if color_type == PALETTE {
+            filtered.push(0);
+            filtered.extend_from_slice(row);
+        } else {
+            filter_row(row, &previous, pixel_size, &mut filtered);
+        }
+        previous.copy_from_slice(row);
+    }
+
+    // keep IDAT chunks to a size decoders comfortably buffer
+    for data in zlib::compress(&filtered).chunks(1 << 20) {
+        write_chunk(&mut writer, b"IDAT", data)?;
+    }
+    write_chunk(&mut writer, b"IEND", &[])?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+// Appends the filter type and the row filtered whichever way gives the smallest
+// sum of absolute differences, the usual heuristic for what compresses best.
+fn filter_row(row: &[u8], previous: &[u8], pixel_size: usize, filtered: &mut Vec<u8>) {
+    let left = | i: usize, bytes: &[u8] | if i >= pixel_size { bytes[i - pixel_size] } else { 0 };
+    let predict = | filter: u8, i: usize | match filter {
+        0 => 0,
+        1 => left(i, row),
+        2 => previous[i],
+        3 => ((left(i, row) as u16 + previous[i] as u16) / 2) as u8,
+        _ => paeth(left(i, row), previous[i], left(i, previous)),
+    };
+
+    let filter = (0..5)
+        .min_by_key(| &filter | {
+            (0..row.len())
+                .map(| i | (row[i].wrapping_sub(predict(filter, i)) as i8).unsigned_abs() as u64)
+                .sum::<u64>()
+        })
+        .unwrap();
+
+    filtered.push(filter);
+    filtered.extend((0..row.len()).map(| i | row[i].wrapping_sub(predict(filter, i))));
+}
+
+fn write_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<(), TargaError> {
+    let mut checked = Vec::with_capacity(4 + data.len());
+    checked.extend_from_slice(kind);
+    checked.extend_from_slice(data);
+
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(&checked)?;
+    writer.write_all(&crc32(&checked).to_be_bytes())?;
+
+    Ok(())
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+
+    table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, | crc, &byte | CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(image: &TargaImage) -> TargaImage {
+        let mut encoded = Vec::new();
+        encode(&mut encoded, image).unwrap();
+
+        let options = ReadOptions { keep_indexed: true, origin: image.origin, format: None };
+        decode(&encoded[..], &options).unwrap()
+    }
+
+    #[test]
+    fn native_formats_round_trip() {
+        let bytes: Vec<u8> = (0..(4 * 7 * 5)).map(| i: u32 | (i * 37 % 256) as u8).collect();
+        let rgba = TargaImage::new(bytes, 7, 5);
+
+        for format in [PixelFormat::Gray8, PixelFormat::GrayA8, PixelFormat::Rgb8, PixelFormat::Rgba8] {
+            let image = rgba.convert(format);
+            assert_eq!(round_trip(&image), image);
+        }
+
+        let mut indexed = rgba.convert(PixelFormat::Gray8);
+        indexed.bytes.iter_mut().for_each(| index | *index %= 3);
+        indexed.format = PixelFormat::Indexed8;
+        indexed.color_map = Some(ColorMap {
+            first_entry: 0,
+            entries: vec![[255, 0, 0, 255], [0, 255, 0, 128], [0, 0, 255, 255]],
+        });
+        assert_eq!(round_trip(&indexed), indexed);
+    }
+
+    #[test]
+    fn interlaced_low_depth_images_decode() {
+        // 1-bit grayscale 3x3, interlaced; every pass row is unfiltered
+        // and the data is one stored deflate block
+        let rows: [&[u8]; 6] = [&[0, 0x80], &[0, 0x80], &[0, 0x40], &[0, 0x80], &[0, 0x40], &[0, 0x40]];
+        let filtered: Vec<u8> = rows.concat();
+        let mut idat = vec![0x78, 0x01, 0x01, filtered.len() as u8, 0, !(filtered.len() as u8), 0xff];
+        idat.extend_from_slice(&filtered);
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in &filtered {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        idat.extend_from_slice(&(b << 16 | a).to_be_bytes());
+
+        let mut data = SIGNATURE.to_vec();
+        write_chunk(&mut data, b"IHDR", &[0, 0, 0, 3, 0, 0, 0, 3, 1, GRAY, 0, 0, 1]).unwrap();
+        write_chunk(&mut data, b"IDAT", &idat).unwrap();
+        write_chunk(&mut data, b"IEND", &[]).unwrap();
+
+        let options = ReadOptions { origin: Origin::TopLeft, format: None, ..Default::default() };
+        let image = decode_png(&data, &options).unwrap();
+        assert_eq!(image.format, PixelFormat::Gray8);
+        // passes 1, 4, 5, 6 and 7 cover the 3x3 pixels in that order
+        assert_eq!(image.bytes, [255, 255, 255, 0, 255, 0, 0, 0, 255]);
+
+        data[20] ^= 1;
+        assert!(matches!(decode_png(&data, &options), Err(TargaError::InvalidData(_))));
+    }
+
+    #[test]
+    fn mutated_chunks_do_not_panic() {
+        let bytes: Vec<u8> = (0..(4 * 16 * 16)).map(| i: u32 | (i * i % 253) as u8).collect();
+        let mut encoded = Vec::new();
+        encode(&mut encoded, &TargaImage::new(bytes, 16, 16)).unwrap();
+
+        let mut state = 0x9e3779b97f4a7c15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        for _ in 0..5_000 {
+            let mut sample = encoded.clone();
+            for _ in 0..(1 + next() % 4) {
+                let index = 8 + next() % (sample.len() - 8);
+                sample[index] = next() as u8;
+            }
+
+            // recompute every checksum, so the damage reaches the chunk contents
+            let mut offset = 8;
+            while let Some(prefix) = sample.get(offset..(offset + 4)) {
+                let length = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+                let Some(end) = offset.checked_add(length + 8).filter(| &end | end + 4 <= sample.len()) else {
+                    break;
+                };
+                let crc = crc32(&sample[(offset + 4)..end]);
+                sample[end..(end + 4)].copy_from_slice(&crc.to_be_bytes());
+                offset = end + 4;
+            }
+
+            let _ = decode_png(&sample, &ReadOptions::default());
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Netpbm bitmaps, graymaps and pixmaps, in both their ASCII (P1 to P3)
+//! and binary (P4 to P6) forms.
+
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use crate::{Origin, PixelFormat, ReadOptions, TargaError, TargaImage};
+
+pub fn decode<R: Read>(mut reader: R, options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    let mut data = Vec::new();
+    let _read = reader.read_to_end(&mut data)?;
+
+    decode_pnm(&data, options)
+}
+
+struct Scanner<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Scanner<'_> {
+    // Skips whitespace and comments, which run from '#' to the end of the line.
+    fn skip_space(&mut self) {
+        while let Some(&byte) = self.data.get(self.position) {
+            if byte == b'#' {
+                while self.data.get(self.position).is_some_and(| &b | b != b'\n' && b != b'\r') {
+                    self.position += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<u32, TargaError> {
+        self.skip_space();
+        let digits = self.data[self.position..].iter().take_while(| b | b.is_ascii_digit()).count();
+        if digits == 0 {
+            return Err(TargaError::InvalidData("expected a number in PNM data"));
+        }
+        let number = self.data[self.position..(self.position + digits)]
+            .iter()
+            .try_fold(0u32, | n, &digit | n.checked_mul(10)?.checked_add((digit - b'0') as u32))
+            .ok_or(TargaError::InvalidData("number too large in PNM data"))?;
+        self.position += digits;
+
+        Ok(number)
+    }
+
+    // PBM allows bits to run together, so each is read as one digit.
+    fn bit(&mut self) -> Result<u32, TargaError> {
+        self.skip_space();
+        match self.data.get(self.position) {
+            Some(&digit @ (b'0' | b'1')) => {
+                self.position += 1;
+                Ok((digit - b'0') as u32)
+            },
+            Some(_) => Err(TargaError::InvalidData("expected 0 or 1 in PBM data")),
+            None => Err(TargaError::ShortPixelData),
+        }
+    }
+}
+
+pub(crate) fn decode_pnm(data: &[u8], options: &ReadOptions) -> Result<TargaImage, TargaError> {
+    let kind = match data {
+        [b'P', kind @ b'1'..=b'6', ..] => kind - b'0',
+        [b'P', b'7', ..] => return Err(TargaError::Unsupported("PAM images")),
+        _ => return Err(TargaError::InvalidData("missing PNM signature")),
+    };
+    let mut scanner = Scanner { data, position: 2 };
+    let width = scanner.number()?;
+    let height = scanner.number()?;
+    let max = if kind == 1 || kind == 4 { 1 } else { scanner.number()? };
+    if !(1..=u16::MAX as u32).contains(&max) {
+        return Err(TargaError::InvalidData("bad PNM maximum value"));
+    }
+    let count = crate::pixel_count(width, height)?;
+
+    let (format, channels) = match kind {
+        3 | 6 => (PixelFormat::Rgb8, 3),
+        _ => (PixelFormat::Gray8, 1),
+    };
+    let samples = channels * count;
+    let scale = | value: u32 | -> Result<u8, TargaError> {
+        if value > max {
+            return Err(TargaError::InvalidData("PNM sample above the maximum value"));
+        }
+        Ok(((value * 255 + max / 2) / max) as u8)
+    };
+
+    let bytes = if kind <= 3 {
+        // every ASCII sample takes at least one byte, which bounds the allocation
+        if data.len() - scanner.position < samples {
+            return Err(TargaError::ShortPixelData);
+        }
+        let mut bytes = Vec::with_capacity(samples);
+        for _ in 0..samples {
+            let value = match kind {
+                1 => 255 * (1 - scanner.bit()?) as u8,
+                _ => {
+                    scanner.skip_space();
+                    if scanner.position == data.len() {
+                        return Err(TargaError::ShortPixelData);
+                    }
+                    scale(scanner.number()?)?
+                },
+            };
+            bytes.push(value);
+        }
+        bytes
+    } else {
+        // a single whitespace character separates the header from binary data
+        let start = scanner.position + 1;
+        let pixels = data.get(start..).ok_or(TargaError::ShortPixelData)?;
+        if !data[scanner.position].is_ascii_whitespace() {
+            return Err(TargaError::InvalidData("bad PNM header"));
+        }
+
+        match kind {
+            4 => {
+                let stride = (width as usize).div_ceil(8);
+                let pixels = pixels.get(..(stride * height as usize)).ok_or(TargaError::ShortPixelData)?;
+                pixels
+                    .chunks_exact(stride)
+                    .flat_map(| row | (0..width as usize).map(| x | row[x / 8] << (x % 8) & 0x80))
+                    .map(| black | if black != 0 { 0 } else { 255 })
+                    .collect()
+            },
+            _ if max > 255 => pixels
+                .get(..(2 * samples))
+                .ok_or(TargaError::ShortPixelData)?
+                .chunks_exact(2)
+                .map(| sample | scale(u16::from_be_bytes([sample[0], sample[1]]) as u32))
+                .collect::<Result<_, _>>()?,
+            _ => pixels
+                .get(..samples)
+                .ok_or(TargaError::ShortPixelData)?
+                .iter()
+                .map(| &sample | scale(sample as u32))
+                .collect::<Result<_, _>>()?,
+        }
+    };
+
+    let image = TargaImage {
+        bytes,
+        width,
+        height,
+        format,
+        color_map: None,
+        origin: Origin::TopLeft,
+        metadata: None,
+    };
+
+    crate::apply_options(image, options)
+}
+
+/// Encodes `image` as a binary PGM if it is grayscale and a binary PPM
+/// otherwise, dropping any alpha channel.
+pub fn encode<W: Write>(writer: W, image: &TargaImage) -> Result<(), TargaError> {
+    write_pnm(writer, image, false)
+}
+
+/// Encodes `image` like `encode`, but as ASCII PGM or PPM.
+pub fn encode_ascii<W: Write>(writer: W, image: &TargaImage) -> Result<(), TargaError> {
+    write_pnm(writer, image, true)
+}
+
+fn write_pnm<W: Write>(mut writer: W, image: &TargaImage, ascii: bool) -> Result<(), TargaError> {
+    let count = crate::pixel_count(image.width, image.height)?;
+    if image.bytes.len() != image.pixel_size() * count {
+        return Err(TargaError::ShortPixelData);
+    }
+
+    let mut image = match image.format {
+        PixelFormat::Gray8 | PixelFormat::Rgb8 => Cow::Borrowed(image),
+        PixelFormat::GrayA8 => Cow::Owned(image.convert(PixelFormat::Gray8)),
+        PixelFormat::Indexed8 | PixelFormat::Indexed16 => {
+            Cow::Owned(image.expand_color_map().convert(PixelFormat::Rgb8))
+        },
+        _ => Cow::Owned(image.convert(PixelFormat::Rgb8)),
+    };
+    if image.origin != Origin::TopLeft {
+        image.to_mut().set_origin(Origin::TopLeft);
+    }
+
+    let kind = match (image.format, ascii) {
+        (PixelFormat::Gray8, true) => 2,
+        (_, true) => 3,
+        (PixelFormat::Gray8, false) => 5,
+        (_, false) => 6,
+    };
+    write!(writer, "P{kind}\n{} {}\n255\n", image.width, image.height)?;
+
+    if ascii {
+        // lines of at most 70 characters, as the format asks
+        let stride = image.pixel_size() * image.width as usize;
+        for row in image.bytes.chunks_exact(stride) {
+            for line in row.chunks(17) {
+                let text: Vec<String> = line.iter().map(u8::to_string).collect();
+                writeln!(writer, "{}", text.join(" "))?;
+            }
+        }
+    } else {
+        writer.write_all(&image.bytes)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings_round_trip() {
+        let bytes: Vec<u8> = (0..(4 * 6 * 4)).map(| i: u32 | (i * 71 % 256) as u8).collect();
+        let mut rgba = TargaImage::new(bytes, 6, 4);
+        rgba.origin = Origin::TopLeft;
+        let options = ReadOptions { origin: Origin::TopLeft, format: None, ..Default::default() };
+
+        for format in [PixelFormat::Gray8, PixelFormat::Rgb8] {
+            let image = rgba.convert(format);
+            for ascii in [false, true] {
+                let mut encoded = Vec::new();
+                write_pnm(&mut encoded, &image, ascii).unwrap();
+                assert_eq!(decode(&encoded[..], &options).unwrap(), image);
+            }
+        }
+    }
+
+    #[test]
+    fn headers_comments_and_depths_decode() {
+        let options = ReadOptions { origin: Origin::TopLeft, format: None, ..Default::default() };
+
+        let bitmap = decode_pnm(b"P1\n# a comment\n3 2\n101\n0 1 0\n", &options).unwrap();
+        assert_eq!(bitmap.bytes, [0, 255, 0, 255, 0, 255]);
+
+        let packed = decode_pnm(b"P4 3 2\n\xa0\x40", &options).unwrap();
+        assert_eq!(packed.bytes, bitmap.bytes);
+
+        let wide = decode_pnm(b"P5 2 1 65535\n\xff\xff\x80\x00", &options).unwrap();
+        assert_eq!(wide.bytes, [255, 128]);
+
+        let scaled = decode_pnm(b"P2 3 1 4 0 2 4", &options).unwrap();
+        assert_eq!(scaled.bytes, [0, 128, 255]);
+
+        assert!(decode_pnm(b"P2 3 1 4 0 2 5", &options).is_err());
+        assert!(decode_pnm(b"P3 1 1 255 1 2", &options).is_err());
+    }
+}
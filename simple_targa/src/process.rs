@@ -0,0 +1,377 @@
+use crate::{AttributeType, PixelFormat, TargaError, TargaImage};
+
+/// Reconstruction filter for `TargaImage::resize`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Filter {
+    /// Averages the source pixels under each destination pixel, or repeats
+    /// the nearest one when enlarging.
+    Box,
+    #[default]
+    Bilinear,
+    /// Windowed sinc with three lobes; sharpest, but may ring at hard edges.
+    Lanczos3,
+}
+
+impl Filter {
+    fn radius(self) -> f32 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        match self {
+            Filter::Box => if (-0.5..0.5).contains(&x) { 1.0 } else { 0.0 },
+            Filter::Bilinear => (1.0 - x.abs()).max(0.0),
+            Filter::Lanczos3 if x.abs() < 3.0 => sinc(x) * sinc(x / 3.0),
+            Filter::Lanczos3 => 0.0,
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = std::f32::consts::PI * x;
+        x.sin() / x
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ResizeOptions {
+    pub filter: Filter,
+    /// Filter colours in linear light, decoding and re-encoding the sRGB
+    /// transfer curve; alpha is always linear.
+    pub srgb: bool,
+    /// The colours are already premultiplied by alpha. Otherwise they are
+    /// weighted by alpha while filtering, so transparent pixels do not bleed
+    /// their colour into their neighbours.
+    pub premultiplied: bool,
+}
+
+impl TargaImage {
+    /// Resamples the image to `width` x `height`. The result keeps the pixel
+    /// format, except that indexed images become `Rgba8`; the same input and
+    /// options always give the same bytes. Fails if either this image or the
+    /// resized one is empty, or would be too large to address.
+    pub fn resize(&self, width: u32, height: u32, options: &ResizeOptions) -> Result<TargaImage, TargaError> {
+        crate::pixel_count(self.width, self.height)?;
+        let count = crate::pixel_count(width, height)?;
+        let format = if self.format.is_indexed() { PixelFormat::Rgba8 } else { self.format };
+        let source = self.convert(PixelFormat::Rgba8);
+        let mut pixels = to_linear(&source.bytes, options);
+
+        let (source_width, source_height) = (self.width as usize, self.height as usize);
+        let columns = weights(source_width, width as usize, options.filter);
+        pixels = resample(&pixels, source_height, &columns, | row, x | row * source_width + x);
+        let rows = weights(source_height, height as usize, options.filter);
+        let pixels = resample(&pixels, width as usize, &rows, | column, y | y * width as usize + column);
+        // the vertical pass produced columns, so put them back into rows
+        let pixels: Vec<[f32; 4]> = (0..count)
+            .map(| i | pixels[(i % width as usize) * height as usize + i / width as usize])
+            .collect();
+
+        let resized = TargaImage {
+            bytes: from_linear(&pixels, options),
+            width,
+            height,
+            format: PixelFormat::Rgba8,
+            color_map: None,
+            ..self.clone_without_pixels()
+        };
+
+        Ok(resized.convert(format))
+    }
+
+    /// Every mip level from the image itself down to 1x1, each half the size
+    /// of the one before, rounding down. An empty image is its only level.
+    pub fn mip_chain(&self, options: &ResizeOptions) -> Vec<TargaImage> {
+        let mut chain = vec![self.clone()];
+        let (mut width, mut height) = (self.width, self.height);
+
+        while (width > 1 || height > 1) && width != 0 && height != 0 {
+            width = (width / 2).max(1);
+            height = (height / 2).max(1);
+            let level = chain.last().unwrap().resize(width, height, options).expect("mip levels are smaller than the image");
+            chain.push(level);
+        }
+
+        chain
+    }
+
+    /// Multiplies the colour channels by alpha, in place. Colour-mapped images
+    /// have their colour map premultiplied instead.
+    pub fn premultiply(&mut self) {
+        self.map_alpha(AttributeType::PremultipliedAlpha, | color, alpha | {
+            ((color as u32 * alpha as u32 + 127) / 255) as u8
+        });
+    }
+
+    /// Divides the colour channels by alpha, in place, undoing `premultiply`
+    /// as far as the rounding allows. Fully transparent pixels become black.
+    pub fn unpremultiply(&mut self) {
+        self.map_alpha(AttributeType::Alpha, | color, alpha | match alpha {
+            0 => 0,
+            _ => ((color as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8,
+        });
+    }
+
+    fn map_alpha(&mut self, attribute_type: AttributeType, f: impl Fn(u8, u8) -> u8) {
+        let map = | [r, g, b, a]: [u8; 4] | [f(r, a), f(g, a), f(b, a), a];
+
+        match self.format {
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 => {
+                for pixel in self.bytes.chunks_exact_mut(4) {
+                    let [r, g, b, a] = map([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                    pixel.copy_from_slice(&[r, g, b, a]);
+                }
+            },
+            PixelFormat::GrayA8 => {
+                for pixel in self.bytes.chunks_exact_mut(2) {
+                    pixel[0] = f(pixel[0], pixel[1]);
+                }
+            },
+            PixelFormat::Rgba5551 => {
+                let mut bytes = Vec::with_capacity(self.bytes.len());
+                for pixel in self.bytes.chunks_exact(2) {
+                    self.format.push_rgba(map(self.format.to_rgba(pixel)), &mut bytes);
+                }
+                self.bytes = bytes;
+            },
+            PixelFormat::Indexed8 | PixelFormat::Indexed16 => {
+                for entry in self.color_map.iter_mut().flat_map(| map | &mut map.entries) {
+                    *entry = map(*entry);
+                }
+            },
+            PixelFormat::Gray8 | PixelFormat::Rgb8 | PixelFormat::Rgb565 => return,
+        }
+
+        if let Some(metadata) = &mut self.metadata {
+            metadata.attribute_type = attribute_type;
+        }
+    }
+}
+
+// For each destination index, the first source index it reads and the
+// normalised weights of that and the following source indices.
+fn weights(source: usize, destination: usize, filter: Filter) -> Vec<(usize, Vec<f32>)> {
+    let scale = source as f32 / destination as f32;
+    // widen the filter when shrinking, so every source pixel contributes
+    let stretch = scale.max(1.0);
+    let support = filter.radius() * stretch;
+    let last_index = source as isize - 1;
+
+    (0..destination)
+        .map(| i | {
+            let center = (i as f32 + 0.5) * scale;
+            let first = (center - support).floor() as isize;
+            let last = (center + support).ceil() as isize;
+
+            // taps beyond the edges repeat the edge pixels
+            let low = first.clamp(0, last_index);
+            let mut taps = vec![0.0; (last.clamp(0, last_index) - low) as usize + 1];
+            for j in first..=last {
+                let weight = filter.weight((j as f32 + 0.5 - center) / stretch);
+                taps[(j.clamp(0, last_index) - low) as usize] += weight;
+            }
+            let start = taps.iter().position(| &w | w != 0.0).unwrap_or(0);
+            let end = taps.iter().rposition(| &w | w != 0.0).map_or(start + 1, | end | end + 1);
+            let total: f32 = taps[start..end].iter().sum();
+            let taps = taps[start..end].iter().map(| w | if total != 0.0 { w / total } else { 0.0 }).collect();
+
+            (low as usize + start, taps)
+        })
+        .collect()
+}
+
+// Filters `lines` lines of pixels along their length, `index` locating
+// pixel `i` of a line. The result holds each line contiguously.
+fn resample(
+    pixels: &[[f32; 4]],
+    lines: usize,
+    weights: &[(usize, Vec<f32>)],
+    index: impl Fn(usize, usize) -> usize,
+) -> Vec<[f32; 4]> {
+    let mut resampled = Vec::with_capacity(lines * weights.len());
+
+    for line in 0..lines {
+        for (start, taps) in weights {
+            let mut sum = [0.0f32; 4];
+            for (k, &weight) in taps.iter().enumerate() {
+                let pixel = pixels[index(line, start + k)];
+                for (total, value) in sum.iter_mut().zip(pixel) {
+                    *total += weight * value;
+                }
+            }
+            resampled.push(sum);
+        }
+    }
+
+    resampled
+}
+
+// Premultiplied, and linear when the options ask for sRGB.
+fn to_linear(bytes: &[u8], options: &ResizeOptions) -> Vec<[f32; 4]> {
+    bytes
+        .chunks_exact(4)
+        .map(| pixel | {
+            let alpha = pixel[3] as f32 / 255.0;
+            let color = | c: u8 | {
+                let c = c as f32 / 255.0;
+                match (options.premultiplied, options.srgb) {
+                    (true, false) => c,
+                    // the transfer curve applies to the colour, not to its product with alpha
+                    (true, true) if alpha > 0.0 => decode_channel(c / alpha, true) * alpha,
+                    (true, true) => 0.0,
+                    (false, srgb) => decode_channel(c, srgb) * alpha,
+                }
+            };
+
+            [color(pixel[0]), color(pixel[1]), color(pixel[2]), alpha]
+        })
+        .collect()
+}
+
+fn from_linear(pixels: &[[f32; 4]], options: &ResizeOptions) -> Vec<u8> {
+    let quantize = | value: f32 | (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+    pixels
+        .iter()
+        .flat_map(| &[r, g, b, a] | {
+            let alpha = a.clamp(0.0, 1.0);
+            let color = | c: f32 | {
+                if alpha == 0.0 {
+                    0
+                } else if options.premultiplied {
+                    quantize(encode_channel(c / alpha, options.srgb) * alpha)
+                } else {
+                    quantize(encode_channel(c / alpha, options.srgb))
+                }
+            };
+
+            [color(r), color(g), color(b), quantize(alpha)]
+        })
+        .collect()
+}
+
+fn decode_channel(value: f32, srgb: bool) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if !srgb {
+        value
+    } else if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn encode_channel(value: f32, srgb: bool) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if !srgb {
+        value
+    } else if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(values: &[u8], width: u16, height: u16) -> TargaImage {
+        let bytes = values.iter().flat_map(| &v | [v, v, v, 255]).collect();
+        TargaImage::new(bytes, width, height)
+    }
+
+    fn red(image: &TargaImage) -> Vec<u8> {
+        image.bytes.chunks(4).map(| p | p[0]).collect()
+    }
+
+    #[test]
+    fn filters_average_and_interpolate() {
+        let image = gray(&[0, 100, 200, 40, 60, 80, 10, 30], 4, 2);
+        let box_filter = ResizeOptions { filter: Filter::Box, ..Default::default() };
+
+        let half = image.resize(2, 1, &box_filter).unwrap();
+        assert_eq!(red(&half), [60, 70]);
+        assert_eq!(red(&image.resize(8, 2, &box_filter).unwrap())[..8], [0, 0, 100, 100, 200, 200, 40, 40]);
+
+        // a constant image stays constant whatever the filter
+        let flat = gray(&[77; 30], 6, 5);
+        for filter in [Filter::Box, Filter::Bilinear, Filter::Lanczos3] {
+            let options = ResizeOptions { filter, ..Default::default() };
+            assert_eq!(red(&flat.resize(4, 7, &options).unwrap()), [77; 28]);
+            assert_eq!(red(&flat.resize(13, 2, &options).unwrap()), [77; 26]);
+        }
+
+        let line = gray(&[0, 255], 2, 1);
+        let wide = line.resize(4, 1, &ResizeOptions::default()).unwrap();
+        assert_eq!(red(&wide), [0, 64, 191, 255]);
+
+        assert!(matches!(line.resize(0, 1, &box_filter), Err(TargaError::BadDimensions { .. })));
+        assert!(matches!(line.resize(u32::MAX, 3, &box_filter), Err(TargaError::BadDimensions { .. })));
+        let empty = TargaImage::new(Vec::new(), 0, 3);
+        assert!(matches!(
+            empty.resize(2, 2, &box_filter),
+            Err(TargaError::BadDimensions { width: 0, height: 3 })
+        ));
+    }
+
+    #[test]
+    fn srgb_downsampling_averages_light() {
+        let checker = gray(&[0, 255, 255, 0], 2, 2);
+        let box_filter = ResizeOptions { filter: Filter::Box, ..Default::default() };
+
+        assert_eq!(red(&checker.resize(1, 1, &box_filter).unwrap()), [128]);
+        // half the light of white is sRGB 188, not 128
+        assert_eq!(red(&checker.resize(1, 1, &ResizeOptions { srgb: true, ..box_filter }).unwrap()), [188]);
+    }
+
+    #[test]
+    fn transparent_pixels_do_not_bleed() {
+        let image = TargaImage::new(vec![255, 0, 0, 255, 0, 255, 0, 0], 2, 1);
+        let box_filter = ResizeOptions { filter: Filter::Box, ..Default::default() };
+
+        assert_eq!(image.resize(1, 1, &box_filter).unwrap().bytes, [255, 0, 0, 128]);
+
+        let mut premultiplied = image.clone();
+        premultiplied.premultiply();
+        let resized = premultiplied.resize(1, 1, &ResizeOptions { premultiplied: true, ..box_filter }).unwrap();
+        assert_eq!(resized.bytes, [128, 0, 0, 128]);
+    }
+
+    #[test]
+    fn mip_chains_end_at_one_pixel() {
+        let image = gray(&(0..60).map(| v | v * 4).collect::<Vec<_>>(), 10, 6);
+        let chain = image.mip_chain(&ResizeOptions { filter: Filter::Box, ..Default::default() });
+
+        let sizes: Vec<_> = chain.iter().map(| level | (level.width, level.height)).collect();
+        assert_eq!(sizes, [(10, 6), (5, 3), (2, 1), (1, 1)]);
+        assert_eq!(chain[0], image);
+        assert_eq!(chain, image.mip_chain(&ResizeOptions { filter: Filter::Box, ..Default::default() }));
+
+        let gray8 = image.convert(PixelFormat::Gray8);
+        assert!(gray8.mip_chain(&ResizeOptions::default()).iter().all(| level | level.format == PixelFormat::Gray8));
+        assert_eq!(TargaImage::new(Vec::new(), 4, 0).mip_chain(&ResizeOptions::default()).len(), 1);
+    }
+
+    #[test]
+    fn premultiplication_round_trips_opaque_and_scales_translucent() {
+        let mut image = TargaImage::new(vec![200, 100, 50, 255, 200, 100, 50, 128, 9, 9, 9, 0], 3, 1);
+        image.premultiply();
+        assert_eq!(image.bytes, [200, 100, 50, 255, 100, 50, 25, 128, 0, 0, 0, 0]);
+
+        image.unpremultiply();
+        assert_eq!(image.bytes, [200, 100, 50, 255, 199, 100, 50, 128, 0, 0, 0, 0]);
+
+        let mut opaque = gray(&[1, 2, 3], 3, 1).convert(PixelFormat::Rgb8);
+        let before = opaque.clone();
+        opaque.premultiply();
+        assert_eq!(opaque, before);
+    }
+}
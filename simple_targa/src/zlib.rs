@@ -0,0 +1,445 @@
+//! zlib streams (RFC 1950) of DEFLATE data (RFC 1951), as far as PNG needs them.
+
+use crate::TargaError;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// order in which dynamic blocks list the code length code lengths
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Inflates a zlib stream, failing if it would produce more than `limit` bytes.
+pub(crate) fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, TargaError> {
+    let header = data.get(..2).ok_or(TargaError::InvalidData("truncated zlib header"))?;
+    if header[0] & 0x0f != 8 || header[0] >> 4 > 7 || u16::from_be_bytes([header[0], header[1]]) % 31 != 0 {
+        return Err(TargaError::InvalidData("bad zlib header"));
+    }
+    if header[1] & 0x20 != 0 {
+        return Err(TargaError::Unsupported("zlib preset dictionary"));
+    }
+
+    let mut reader = BitReader { data, position: 2, buffer: 0, count: 0 };
+    let mut output = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored(&mut reader, &mut output, limit)?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                codes(&mut reader, &mut output, limit, &literals, &distances)?;
+            },
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut reader)?;
+                codes(&mut reader, &mut output, limit, &literals, &distances)?;
+            },
+            _ => return Err(TargaError::InvalidData("bad deflate block type")),
+        }
+        if last {
+            break;
+        }
+    }
+
+    let position = reader.position;
+    let checksum = data
+        .get(position..(position + 4))
+        .ok_or(TargaError::InvalidData("truncated zlib checksum"))?;
+    if u32::from_be_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]) != adler32(&output) {
+        return Err(TargaError::InvalidData("zlib checksum mismatch"));
+    }
+
+    Ok(output)
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, TargaError> {
+        while self.count < count {
+            let byte = *self.data.get(self.position).ok_or(TargaError::InvalidData("truncated deflate stream"))?;
+            self.buffer |= (byte as u32) << self.count;
+            self.position += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+// Canonical Huffman code, stored as the number of codes of each length
+// and the symbols ordered by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, TargaError> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = 2 * left - count as i32;
+            if left < 0 {
+                return Err(TargaError::InvalidData("over-subscribed Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Ok(Huffman { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, TargaError> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for &count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(TargaError::InvalidData("bad Huffman code"))
+    }
+}
+
+fn stored(reader: &mut BitReader, output: &mut Vec<u8>, limit: usize) -> Result<(), TargaError> {
+    reader.align();
+    let position = reader.position;
+    let header = reader
+        .data
+        .get(position..(position + 4))
+        .ok_or(TargaError::InvalidData("truncated stored block"))?;
+    let length = u16::from_le_bytes([header[0], header[1]]);
+    if length != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err(TargaError::InvalidData("bad stored block length"));
+    }
+
+    let start = position + 4;
+    let bytes = reader
+        .data
+        .get(start..(start + length as usize))
+        .ok_or(TargaError::InvalidData("truncated stored block"))?;
+    if output.len() + bytes.len() > limit {
+        return Err(TargaError::InvalidData("decompressed data too long"));
+    }
+    output.extend_from_slice(bytes);
+    reader.position = start + length as usize;
+
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    // the fixed lengths are known to be complete
+    (Huffman::new(&lengths).unwrap(), Huffman::new(&[5; 30]).unwrap())
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), TargaError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(TargaError::InvalidData("bad dynamic block code counts"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (length, repeat) = match code_length_code.decode(reader)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or(TargaError::InvalidData("repeat with no previous length"))?;
+                (previous, 3 + reader.bits(2)?)
+            },
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        if lengths.len() + repeat as usize > literal_count + distance_count {
+            return Err(TargaError::InvalidData("code lengths overflow"));
+        }
+        lengths.extend(std::iter::repeat_n(length, repeat as usize));
+    }
+    if lengths[256] == 0 {
+        return Err(TargaError::InvalidData("missing end-of-block code"));
+    }
+
+    Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+fn codes(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    limit: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), TargaError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => {
+                if output.len() >= limit {
+                    return Err(TargaError::InvalidData("decompressed data too long"));
+                }
+                output.push(symbol as u8);
+            },
+            256 => return Ok(()),
+            _ => {
+                let symbol = symbol - 257;
+                if symbol >= LENGTH_BASE.len() {
+                    return Err(TargaError::InvalidData("bad length code"));
+                }
+                let length = LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+                let symbol = distances.decode(reader)? as usize;
+                if symbol >= DISTANCE_BASE.len() {
+                    return Err(TargaError::InvalidData("bad distance code"));
+                }
+                let distance = DISTANCE_BASE[symbol] as usize + reader.bits(DISTANCE_EXTRA[symbol] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(TargaError::InvalidData("distance too far back"));
+                }
+                if output.len() + length > limit {
+                    return Err(TargaError::InvalidData("decompressed data too long"));
+                }
+
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            },
+        }
+    }
+}
+
+/// Deflates `data` into a zlib stream, using one block of the fixed
+/// Huffman codes and greedy LZ77 matching.
+pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+    const WINDOW: usize = 32768;
+    const HASH_SIZE: usize = 1 << 15;
+    const MAX_CHAIN: usize = 64;
+    const MIN_MATCH: usize = 3;
+    const MAX_MATCH: usize = 258;
+
+    let hash = | i: usize | {
+        ((data[i] as usize) << 10 ^ (data[i + 1] as usize) << 5 ^ data[i + 2] as usize) & (HASH_SIZE - 1)
+    };
+    let mut head = vec![usize::MAX; HASH_SIZE];
+    let mut previous = vec![usize::MAX; WINDOW];
+    let insert = | i: usize, head: &mut [usize], previous: &mut [usize] | {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            previous[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut writer = BitWriter { bytes: vec![0x78, 0x01], buffer: 0, count: 0 };
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let length = data[candidate..]
+                    .iter()
+                    .zip(&data[i..])
+                    .take(MAX_MATCH)
+                    .take_while(| (a, b) | a == b)
+                    .count();
+                if length > best.0 {
+                    best = (length, i - candidate);
+                    if length == MAX_MATCH {
+                        break;
+                    }
+                }
+                let next = previous[candidate % WINDOW];
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        let (length, distance) = best;
+        if length >= MIN_MATCH {
+            writer.length(length);
+            writer.distance(distance);
+            for j in i..(i + length) {
+                insert(j, &mut head, &mut previous);
+            }
+            i += length;
+        } else {
+            writer.literal(data[i] as u16);
+            insert(i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+    writer.literal(256);
+
+    let mut bytes = writer.finish();
+    bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    bytes
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are sent most significant bit first.
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn literal(&mut self, symbol: u16) {
+        match symbol {
+            0..=143 => self.code(0x30 + symbol as u32, 8),
+            144..=255 => self.code(0x190 + symbol as u32 - 144, 9),
+            256..=279 => self.code(symbol as u32 - 256, 7),
+            _ => self.code(0xc0 + symbol as u32 - 280, 8),
+        }
+    }
+
+    fn length(&mut self, length: usize) {
+        let symbol = LENGTH_BASE.iter().rposition(| &base | base as usize <= length).unwrap();
+        self.literal(257 + symbol as u16);
+        self.bits((length - LENGTH_BASE[symbol] as usize) as u32, LENGTH_EXTRA[symbol] as u32);
+    }
+
+    fn distance(&mut self, distance: usize) {
+        let symbol = DISTANCE_BASE.iter().rposition(| &base | base as usize <= distance).unwrap();
+        self.code(symbol as u32, 5);
+        self.bits((distance - DISTANCE_BASE[symbol] as usize) as u32, DISTANCE_EXTRA[symbol] as u32);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressed_data_inflates_back() {
+        let mut data: Vec<u8> = b"a screenshot is mostly runs of the same few colours ".repeat(200);
+        data.extend((0..5000u32).map(| i | (i * i % 251) as u8));
+        data.extend(std::iter::repeat_n(7, 40_000));
+
+        let compressed = compress(&data);
+        assert!(compressed.len() < data.len() / 4);
+        assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+        assert!(decompress(&compressed, data.len() - 1).is_err());
+        assert_eq!(decompress(&compress(&[]), 0).unwrap(), []);
+    }
+
+    #[test]
+    fn stored_and_dynamic_blocks_inflate() {
+        // zlib.compress(b"hello", 0)
+        let stored = [0x78, 0x01, 0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o', 0x06, 0x2c, 0x02, 0x15];
+        assert_eq!(decompress(&stored, 5).unwrap(), b"hello");
+
+        // zlib.compress(data, 9) of the bytes below, which it codes as a dynamic block
+        let data: Vec<u8> = (0..600u32).map(| i | ((i * i * 31 + i / 3) % 23 * 5) as u8 + b'0').collect();
+        let dynamic = [
+            0x78, 0xda, 0xed, 0xcc, 0x11, 0x02, 0xc0, 0x30, 0x0c, 0x00, 0xc0, 0x2f, 0x45, 0x2a, 0x91, 0x4a, 0x65,
+            0x12, 0x89, 0x44, 0x2a, 0x95, 0x70, 0x25, 0x32, 0x89, 0x54, 0x2a, 0xe5, 0x48, 0xde, 0x3a, 0xde, 0x1f,
+            0x7a, 0x0f, 0x38, 0x90, 0xde, 0x6c, 0x59, 0x15, 0x8e, 0x87, 0x52, 0x94, 0x56, 0x9e, 0xee, 0x2f, 0xfb,
+            0xd2, 0xc4, 0x50, 0x37, 0x9a, 0x93, 0x7d, 0x1b, 0x52, 0x5b, 0x00, 0x2f, 0xe0, 0xa1, 0x41, 0xbb, 0xe4,
+            0x8c, 0xb0, 0x52, 0x63, 0x98, 0xa2, 0x30, 0xdc, 0xe4, 0x26, 0xff, 0xe4, 0x03, 0xb0, 0x8d, 0xf1, 0x6e,
+        ];
+        assert_eq!(decompress(&dynamic, data.len()).unwrap(), data);
+
+        let mut corrupt = dynamic;
+        corrupt[40] ^= 0x10;
+        assert!(decompress(&corrupt, data.len()).is_err());
+    }
+}
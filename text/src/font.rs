@@ -1,20 +1,403 @@
-use glium::texture::{RawImage2d, CompressedSrgbTexture2d};
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::HashMap;
+use std::path::Path;
+#[cfg(feature = "truetype")]
+use std::rc::Rc;
+use glium::texture::{RawImage2d, SrgbTexture2d};
 use glium::glutin::surface::WindowSurface;
+use glium::uniforms::Uniforms;
+use simple_targa::{read_image_with, Origin, ReadOptions};
+mod atlas;
+mod distance_field;
+#[cfg(feature = "truetype")]
+mod truetype;
+#[cfg(feature = "truetype")]
+use truetype::Outlines;
 
+/// Pixels to the em that TrueType fonts are rasterized at; text is scaled
+/// to the console's lines from there.
+#[cfg(feature = "truetype")]
+const TRUETYPE_SIZE: f32 = 32.0;
+
+/// Where a glyph is in the atlas and how it sits on a line, in atlas pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Glyph {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// From the pen position to the left of the glyph.
+    pub x_offset: i32,
+    /// From the top of the line to the top of the glyph.
+    pub y_offset: i32,
+    /// How far the pen moves on after the glyph.
+    pub advance: i32,
+}
+
+impl Glyph {
+    /// The glyph's edges in the atlas as left, right, bottom and top, in
+    /// pixels from the atlas's top left.
+    pub fn tex_coords(&self) -> [f32; 4] {
+        [
+            self.x as f32,
+            (self.x + self.width) as f32,
+            (self.y + self.height) as f32,
+            self.y as f32,
+        ]
+    }
+}
+
+/// The glyph table of a font atlas, read from a BMFont text description.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontDescription {
+    pub line_height: u32,
+    /// From the top of a line to its baseline.
+    pub base: u32,
+    /// Width and height of the atlas image.
+    pub scale: (u32, u32),
+    /// The atlas image file, relative to the description.
+    pub page: String,
+    pub glyphs: HashMap<char, Glyph>,
+    /// The glyph shown for characters the font lacks, which BMFont gives the id -1.
+    pub fallback: Option<Glyph>,
+    /// How much closer or further apart to set pairs of characters.
+    pub kerning: HashMap<(char, char), i32>,
+}
+
+impl FontDescription {
+    /// Reads the `common`, `page`, `char` and `kerning` lines of a
+    /// single-page font, ignoring other lines.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut description = FontDescription::default();
+        let mut page = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let error = | message: String | format!("line {}: {message}", number + 1);
+            let (tag, fields) = split_fields(line).map_err(error)?;
+            let field = | name: &str | -> Result<&str, String> {
+                fields
+                    .iter()
+                    .find(| (key, _) | *key == name)
+                    .map(| &(_, value) | value)
+                    .ok_or_else(|| error(format!("`{tag}` has no `{name}`")))
+            };
+            let number = | name: &str | -> Result<i32, String> {
+                let value = field(name)?;
+                value.parse().map_err(| _ | error(format!("`{name}` is not a number: `{value}`")))
+            };
+            let size = | name: &str | -> Result<u32, String> {
+                u32::try_from(number(name)?).map_err(| _ | error(format!("`{name}` is negative")))
+            };
+            let character = | id: i32 | {
+                u32::try_from(id)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| error(format!("{id} is not a character")))
+            };
+
+            match tag {
+                "common" => {
+                    if number("pages")? != 1 {
+                        return Err(error("only fonts with one page are supported".to_string()));
+                    }
+                    description.line_height = size("lineHeight")?;
+                    description.base = size("base")?;
+                    description.scale = (size("scaleW")?, size("scaleH")?);
+                },
+                "page" if number("id")? == 0 => page = Some(field("file")?.to_string()),
+                "char" => {
+                    let glyph = Glyph {
+                        x: size("x")?,
+                        y: size("y")?,
+                        width: size("width")?,
+                        height: size("height")?,
+                        x_offset: number("xoffset")?,
+                        y_offset: number("yoffset")?,
+                        advance: number("xadvance")?,
+                    };
+                    match number("id")? {
+                        -1 => description.fallback = Some(glyph),
+                        id => {
+                            description.glyphs.insert(character(id)?, glyph);
+                        },
+                    }
+                },
+                "kerning" => {
+                    let pair = (character(number("first")?)?, character(number("second")?)?);
+                    description.kerning.insert(pair, number("amount")?);
+                },
+                _ => (),
+            }
+        }
+
+        if description.scale.0 == 0 || description.scale.1 == 0 {
+            return Err("missing `common` line, or an empty atlas".to_string());
+        }
+        if description.line_height == 0 {
+            return Err("`lineHeight` is 0".to_string());
+        }
+        description.page = page.ok_or("missing `page` line")?;
+        let (width, height) = description.scale;
+        let outside = | glyph: &Glyph | {
+            glyph.x.checked_add(glyph.width).is_none_or(| right | right > width)
+                || glyph.y.checked_add(glyph.height).is_none_or(| bottom | bottom > height)
+        };
+        if let Some(ch) = description.glyphs.iter().find(| (_, glyph) | outside(glyph)).map(| (ch, _) | ch) {
+            return Err(format!("the glyph for `{ch}` reaches outside the atlas"));
+        }
+        if description.fallback.as_ref().is_some_and(outside) {
+            return Err("the fallback glyph reaches outside the atlas".to_string());
+        }
+
+        Ok(description)
+    }
+
+    /// The glyph for `ch`, or the fallback glyph if the font lacks it;
+    /// without a fallback, such characters take up no room.
+    pub fn glyph(&self, ch: char) -> Glyph {
+        self.glyphs.get(&ch).or(self.fallback.as_ref()).copied().unwrap_or_default()
+    }
+
+    /// How far to move the pen between `first` and `second`, beyond the
+    /// advance of `first`.
+    pub fn kern(&self, first: char, second: char) -> i32 {
+        self.kerning.get(&(first, second)).copied().unwrap_or(0)
+    }
+
+    /// The width and height, in atlas pixels, that `text` takes up when set
+    /// from the pen's starting position: the widest of its lines' advances,
+    /// kerning included, and the height of all of its lines.
+    pub fn measure(&self, text: &str) -> (f32, f32) {
+        let lines = text.split('\n');
+        let widths = lines.clone().map(| line | {
+            let mut previous = None;
+            line.chars()
+                .map(| ch | {
+                    let kern = previous.map_or(0, | previous | self.kern(previous, ch));
+                    previous = Some(ch);
+                    kern + self.glyph(ch).advance
+                })
+                .sum::<i32>()
+        });
+
+        let width = widths.max().unwrap_or(0).max(0);
+        (width as f32, (lines.count() as u32 * self.line_height) as f32)
+    }
+}
+
+type Fields<'a> = Vec<(&'a str, &'a str)>;
+
+// Splits a line into its tag and `key=value` fields, where values may be quoted.
+fn split_fields(line: &str) -> Result<(&str, Fields<'_>), String> {
+    let line = line.trim_start();
+    let (tag, mut rest) = line.split_at(line.find(char::is_whitespace).unwrap_or(line.len()));
+    rest = rest.trim_start();
+
+    let mut fields = Vec::new();
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=').ok_or(format!("`{rest}` is not a `key=value` field"))?;
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').ok_or("missing closing quote")?,
+            None => after.split_at(after.find(char::is_whitespace).unwrap_or(after.len())),
+        };
+        fields.push((key, value));
+        rest = after.trim_start();
+    }
+
+    Ok((tag, fields))
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontOptions {
+    /// Draws glyphs from signed distance fields reaching this many atlas
+    /// pixels past their edges, which stay sharp at any scale and can be
+    /// given `Effects`.
+    pub distance_field: Option<u32>,
+}
+
+/// An outline and a drop shadow around distance field glyphs, which reach
+/// no further than the font's `distance_field` spread. Bitmap glyphs are
+/// drawn without them.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Effects {
+    /// How far the outline reaches past the glyphs' edges, in atlas pixels;
+    /// 0 for none.
+    pub outline_width: f32,
+    pub outline_colour: [f32; 3],
+    /// How far right and down the shadow falls, in atlas pixels.
+    pub shadow_offset: [f32; 2],
+    /// Transparent for no shadow.
+    pub shadow_colour: [f32; 4],
+}
+
+impl Effects {
+    // Whether the effects stay within `spread` pixels of the glyphs' edges.
+    fn check(&self, spread: u32) -> Result<(), String> {
+        let spread = spread as f32;
+        if !(0.0..=spread).contains(&self.outline_width) {
+            return Err(format!("the outline must be from 0 to {spread} wide"));
+        }
+        if self.shadow_offset.iter().any(| offset | offset.abs() > spread) {
+            return Err(format!("the shadow cannot fall more than {spread} away"));
+        }
+
+        Ok(())
+    }
+}
+
+/// A font's glyph table and the atlas texture its glyphs are drawn from,
+/// stored top row first. TrueType fonts add glyphs to both as text needs them.
 pub struct Font {
-    pub texture: CompressedSrgbTexture2d,
     pub shader: glium::Program,
+    texture: RefCell<SrgbTexture2d>,
+    glyphs: RefCell<FontDescription>,
+    spread: Option<u32>,
+    effects: Cell<Effects>,
+    #[cfg(feature = "truetype")]
+    outlines: Option<(RefCell<Outlines>, Rc<glium::backend::Context>)>,
 }
 
 impl Font {
-    pub fn new(display: &glium::Display<WindowSurface>, targa: simple_targa::TargaImage) -> Self {
-        let image = RawImage2d::from_raw_rgba(targa.bytes, (targa.width, targa.height));
-        let texture = CompressedSrgbTexture2d::new(display, image).unwrap();
+    /// Loads a BMFont text description and the atlas image it names, or,
+    /// with the `truetype` feature, a `.ttf` or `.otf` font.
+    pub fn load(display: &glium::Display<WindowSurface>, path: &Path, options: &FontOptions) -> Result<Self, String> {
+        let extension = path.extension().and_then(| extension | extension.to_str()).map(str::to_lowercase);
+        if let Some("ttf" | "otf") = extension.as_deref() {
+            #[cfg(feature = "truetype")]
+            return Font::load_truetype(display, path, TRUETYPE_SIZE, options);
+            #[cfg(not(feature = "truetype"))]
+            return Err(format!("{}: TrueType fonts need the `truetype` feature", path.display()));
+        }
+
+        let text = std::fs::read_to_string(path).map_err(| error | format!("{}: {error}", path.display()))?;
+        let mut description = FontDescription::parse(&text).map_err(| error | format!("{}: {error}", path.display()))?;
+        let page = path.with_file_name(&description.page);
+        let targa = read_image_with(
+            &page.to_string_lossy(),
+            &ReadOptions { origin: Origin::TopLeft, ..Default::default() },
+        ).map_err(| error | format!("{}: {error}", page.display()))?;
+        if (targa.width, targa.height) != description.scale {
+            return Err(format!("{} is not the size {} describes", page.display(), path.display()));
+        }
+
+        let image = match options.distance_field {
+            Some(spread) => {
+                let atlas = distance_field::repack(&mut description, &targa, spread);
+                let (bytes, size) = atlas.image();
+                RawImage2d::from_raw_rgba(bytes.to_vec(), size)
+            },
+            None => RawImage2d::from_raw_rgba(targa.bytes, (targa.width, targa.height)),
+        };
+
+        Ok(Font {
+            shader: program(display, options),
+            texture: RefCell::new(SrgbTexture2d::new(display, image).unwrap()),
+            glyphs: RefCell::new(description),
+            spread: options.distance_field,
+            effects: Cell::new(Effects::default()),
+            #[cfg(feature = "truetype")]
+            outlines: None,
+        })
+    }
+
+    /// Loads a TrueType or OpenType font to rasterize at `size` pixels to the em.
+    #[cfg(feature = "truetype")]
+    pub fn load_truetype(
+        display: &glium::Display<WindowSurface>,
+        path: &Path,
+        size: f32,
+        options: &FontOptions,
+    ) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(| error | format!("{}: {error}", path.display()))?;
+        let (outlines, description) = Outlines::new(&bytes, size, options.distance_field)
+            .map_err(| error | format!("{}: {error}", path.display()))?;
+        let (bytes, size) = outlines.image();
+        let image = RawImage2d::from_raw_rgba(bytes.to_vec(), size);
+
+        Ok(Font {
+            shader: program(display, options),
+            texture: RefCell::new(SrgbTexture2d::new(display, image).unwrap()),
+            glyphs: RefCell::new(description),
+            spread: options.distance_field,
+            effects: Cell::new(Effects::default()),
+            outlines: Some((RefCell::new(outlines), Rc::clone(glium::backend::Facade::get_context(display)))),
+        })
+    }
+
+    /// Whether glyphs are drawn from distance fields, and so can have `Effects`.
+    pub fn is_distance_field(&self) -> bool {
+        self.spread.is_some()
+    }
+
+    pub fn effects(&self) -> Effects {
+        self.effects.get()
+    }
+
+    /// Fails if the outline or shadow would reach further than the spread.
+    pub fn set_effects(&self, effects: Effects) -> Result<(), String> {
+        effects.check(self.spread.unwrap_or(0))?;
+        self.effects.set(effects);
 
-        let shader = glium::Program::from_source(display, FONT_VS, FONT_FS, None).unwrap();
+        Ok(())
+    }
+
+    /// The uniforms `shader` needs to draw a line of text at `translation`,
+    /// where `texture` is borrowed from `texture()`.
+    pub fn uniforms<'a>(&self, texture: &'a SrgbTexture2d, translation: [f32; 2]) -> impl Uniforms + 'a {
+        let effects = self.effects.get();
+
+        uniform! {
+            translation: translation,
+            font: texture,
+            spread: self.spread.unwrap_or(1) as f32,
+            outline_width: effects.outline_width,
+            outline_colour: effects.outline_colour,
+            shadow_offset: effects.shadow_offset,
+            shadow_colour: effects.shadow_colour,
+        }
+    }
+
+    pub fn texture(&self) -> Ref<'_, SrgbTexture2d> {
+        self.texture.borrow()
+    }
 
-        Font { texture, shader }
+    /// The glyph table, after adding any glyphs `text` needs that the font
+    /// rasterizes on demand.
+    pub fn glyphs<I: IntoIterator<Item = char>>(&self, text: I) -> Ref<'_, FontDescription> {
+        self.rasterize(text);
+
+        self.glyphs.borrow()
+    }
+
+    #[cfg(feature = "truetype")]
+    fn rasterize<I: IntoIterator<Item = char>>(&self, text: I) {
+        let Some((outlines, context)) = &self.outlines else {
+            return;
+        };
+        let mut outlines = outlines.borrow_mut();
+        let mut glyphs = self.glyphs.borrow_mut();
+        let count = glyphs.glyphs.len();
+        for ch in text {
+            if !glyphs.glyphs.contains_key(&ch) {
+                outlines.add(&mut glyphs, ch);
+            }
+        }
+
+        if glyphs.glyphs.len() != count {
+            let (bytes, size) = outlines.image();
+            let image = RawImage2d::from_raw_rgba(bytes.to_vec(), size);
+            *self.texture.borrow_mut() = SrgbTexture2d::new(context, image).unwrap();
+        }
     }
+
+    #[cfg(not(feature = "truetype"))]
+    fn rasterize<I: IntoIterator<Item = char>>(&self, _text: I) {}
+}
+
+fn program(display: &glium::Display<WindowSurface>, options: &FontOptions) -> glium::Program {
+    let fragment = if options.distance_field.is_some() { DISTANCE_FIELD_FS } else { FONT_FS };
+
+    glium::Program::from_source(display, FONT_VS, fragment, None).unwrap()
 }
 
 const FONT_VS: &str = r#"
@@ -24,11 +407,12 @@ const FONT_VS: &str = r#"
     in vec2 tex;
 
     uniform vec2 translation;
+    uniform sampler2D font;
 
     out vec2 coordinates;
 
     void main() {
-        coordinates = tex;
+        coordinates = tex / vec2(textureSize(font, 0));
         gl_Position = vec4(pos + translation, 0.5, 1.0);
     }
 "#;
@@ -48,3 +432,114 @@ const FONT_FS: &str = r#"
         if (colour.a == 0) discard;
     }
 "#;
+
+const DISTANCE_FIELD_FS: &str = r#"
+    #version 150
+
+    in vec2 coordinates;
+
+    uniform sampler2D font;
+    // atlas pixels the distances reach past the glyphs' edges
+    uniform float spread;
+    uniform float outline_width;
+    uniform vec3 outline_colour;
+    uniform vec2 shadow_offset;
+    uniform vec4 shadow_colour;
+
+    out vec4 colour;
+
+    // how much of a pixel is within `edge`, antialiased over the pixel's width
+    float coverage(float distance, float edge) {
+        float width = fwidth(distance);
+        return smoothstep(edge - width, edge + width, distance);
+    }
+
+    void main() {
+        float outline_edge = 0.5 - outline_width / (2.0 * spread);
+        float distance = texture(font, coordinates).a;
+        float glyph = coverage(distance, 0.5);
+        float front = coverage(distance, outline_edge);
+
+        vec2 shadowed = coordinates - shadow_offset / vec2(textureSize(font, 0));
+        float shadow = coverage(texture(font, shadowed).a, outline_edge) * shadow_colour.a * (1.0 - front);
+
+        float alpha = front + shadow;
+        if (alpha == 0) discard;
+
+        vec3 fill = mix(outline_colour, vec3(1.0), glyph / max(front, 0.0001));
+        colour = vec4((fill * front + shadow_colour.rgb * shadow) / alpha, alpha);
+    }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = concat!(
+        "info face=\"a font\" size=12\n",
+        "common lineHeight=14 base=11 scaleW=64 scaleH=32 pages=1\n",
+        "page id=0 file=\"glyphs and such.tga\"\n",
+        "char id=65 x=2 y=3 width=7 height=9 xoffset=-1 yoffset=2 xadvance=8 page=0\n",
+        "char id=86 x=9 y=3 width=7 height=9 xoffset=0 yoffset=2 xadvance=7 page=0\n",
+        "char id=-1 x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=5 page=0\n",
+        "kernings count=1\n",
+        "kerning first=65 second=86 amount=-2\n",
+    );
+
+    #[test]
+    fn descriptions_give_glyph_rectangles() {
+        let description = FontDescription::parse(include_str!("../res/font.fnt")).unwrap();
+        assert_eq!(description.page, "font.tga");
+        assert_eq!(description.glyphs.len(), 95);
+        assert_eq!(description.glyph('é'), description.fallback.unwrap());
+
+        assert_eq!(description.glyph('?').tex_coords(), [340.0, 365.0, 569.0, 523.0]);
+
+        let description = FontDescription::parse(DESCRIPTION).unwrap();
+        assert_eq!(description.page, "glyphs and such.tga");
+        assert_eq!((description.line_height, description.base), (14, 11));
+        let glyph = Glyph { x: 2, y: 3, width: 7, height: 9, x_offset: -1, y_offset: 2, advance: 8 };
+        assert_eq!(description.glyphs[&'A'], glyph);
+
+        assert!(FontDescription::parse("common lineHeight=1 base=1 scaleW=1 scaleH=1 pages=1").is_err());
+        assert!(FontDescription::parse("page id=0 file=a.tga\nchar id=x").unwrap_err().starts_with("line 2"));
+
+        let flat = DESCRIPTION.replace("lineHeight=14", "lineHeight=0");
+        assert_eq!(FontDescription::parse(&flat).unwrap_err(), "`lineHeight` is 0");
+        assert!(FontDescription::parse(&DESCRIPTION.replace("scaleH=32", "scaleH=0")).is_err());
+
+        // glyphs must lie within the atlas, wherever the `common` line comes
+        let past_right = DESCRIPTION.replace("x=9 y=3", "x=58 y=3");
+        assert_eq!(FontDescription::parse(&past_right).unwrap_err(), "the glyph for `V` reaches outside the atlas");
+        let past_bottom = DESCRIPTION.replace("y=0 width=0 height=0", "y=0 width=0 height=33");
+        assert!(FontDescription::parse(&past_bottom).is_err());
+        let common = "common lineHeight=14 base=11 scaleW=16 scaleH=32 pages=1\n";
+        let common_last = DESCRIPTION.replacen("common", "info", 1) + common;
+        assert_eq!(FontDescription::parse(&common_last).unwrap().scale, (16, 32));
+    }
+
+    #[test]
+    fn effects_stay_within_the_spread() {
+        let outline = | width | Effects { outline_width: width, ..Default::default() };
+        assert!(outline(6.0).check(6).is_ok());
+        assert!(outline(6.5).check(6).is_err());
+        assert!(outline(-1.0).check(6).is_err());
+        assert!(outline(1.0).check(0).is_err());
+
+        let shadow = | x, y | Effects { shadow_offset: [x, y], ..Default::default() };
+        assert!(shadow(-6.0, 6.0).check(6).is_ok());
+        assert_eq!(shadow(2.0, 7.0).check(6).unwrap_err(), "the shadow cannot fall more than 6 away");
+    }
+
+    #[test]
+    fn measures_include_kerning() {
+        let description = FontDescription::parse(DESCRIPTION).unwrap();
+        assert_eq!(description.kern('A', 'V'), -2);
+        assert_eq!(description.kern('V', 'A'), 0);
+
+        assert_eq!(description.measure(""), (0.0, 14.0));
+        assert_eq!(description.measure("AV"), (13.0, 14.0));
+        assert_eq!(description.measure("VA"), (15.0, 14.0));
+        assert_eq!(description.measure("A\nVA?"), (20.0, 28.0));
+    }
+}
@@ -0,0 +1,97 @@
+use simple_targa::{Origin, TargaImage};
+
+/// Width of an atlas, which only ever grows taller.
+const ATLAS_WIDTH: u32 = 512;
+/// The tallest an atlas may grow; glyphs that do not fit are left blank.
+const MAX_ATLAS_HEIGHT: u32 = 4096;
+/// Empty pixels around each glyph so neighbours do not bleed into it.
+const PADDING: u32 = 1;
+
+/// White pixels with glyphs' coverage, or distance fields, as alpha, filled
+/// a shelf at a time from the top left.
+pub struct Atlas {
+    image: TargaImage,
+    // where the next glyph goes on the current shelf, and how tall the shelf is so far
+    shelf: (u32, u32, u32),
+}
+
+impl Default for Atlas {
+    fn default() -> Self {
+        Atlas::new(ATLAS_WIDTH, 64)
+    }
+}
+
+impl Atlas {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut image = TargaImage::new(vec![0; (4 * width * height) as usize], width as u16, height as u16);
+        image.origin = Origin::TopLeft;
+
+        Atlas { image, shelf: (0, 0, 0) }
+    }
+
+    /// The atlas as RGBA bytes, top row first, and its width and height.
+    pub fn image(&self) -> (&[u8], (u32, u32)) {
+        (&self.image.bytes, (self.image.width, self.image.height))
+    }
+
+    /// Finds room for a `width` by `height` glyph, growing the atlas if need
+    /// be, or gives `None` if it cannot grow enough.
+    pub fn place(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width == 0 || height == 0 {
+            return Some((0, 0));
+        }
+        let atlas_width = self.image.width;
+        if width + PADDING > atlas_width {
+            return None;
+        }
+
+        let (mut x, mut y, mut shelf_height) = self.shelf;
+        if x + width + PADDING > atlas_width {
+            (x, y, shelf_height) = (0, y + shelf_height, 0);
+        }
+        let mut grown = self.image.height;
+        while y + height + PADDING > grown {
+            grown *= 2;
+        }
+        if grown > MAX_ATLAS_HEIGHT {
+            return None;
+        }
+        self.image.bytes.resize((4 * atlas_width * grown) as usize, 0);
+        self.image.height = grown;
+
+        self.shelf = (x + width + PADDING, y, shelf_height.max(height + PADDING));
+        Some((x, y))
+    }
+
+    /// Copies rows of `width` alpha values into the atlas from `x`, `y` down.
+    pub fn write(&mut self, x: u32, y: u32, width: u32, coverage: &[u8]) {
+        for (row, line) in coverage.chunks(width as usize).enumerate() {
+            for (column, &alpha) in line.iter().enumerate() {
+                self.image.put_pixel(x + column as u32, y + row as u32, &[255, 255, 255, alpha]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atlases_grow_to_fit_their_glyphs() {
+        let mut atlas = Atlas::new(16, 4);
+        assert_eq!(atlas.place(7, 3), Some((0, 0)));
+        assert_eq!(atlas.place(7, 2), Some((8, 0)));
+        assert_eq!(atlas.image().1, (16, 4));
+
+        assert_eq!(atlas.place(4, 5), Some((0, 4)));
+        assert_eq!((atlas.image().1, atlas.image().0.len()), ((16, 16), 4 * 16 * 16));
+        assert_eq!(atlas.place(0, 5), Some((0, 0)));
+        assert_eq!(atlas.place(16, 1), None);
+        assert_eq!(atlas.place(1, MAX_ATLAS_HEIGHT), None);
+
+        atlas.write(8, 0, 2, &[1, 2, 3, 4]);
+        assert_eq!(atlas.image.get_pixel(9, 0), Some(&[255, 255, 255, 2][..]));
+        assert_eq!(atlas.image.get_pixel(8, 1), Some(&[255, 255, 255, 3][..]));
+    }
+}
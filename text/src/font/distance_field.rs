@@ -0,0 +1,167 @@
+use crate::font::atlas::Atlas;
+use crate::font::FontDescription;
+use simple_targa::TargaImage;
+
+/// Stands in for the distance to a feature there is none of.
+const FAR: f64 = 1e20;
+
+/// Turns a `width` by `height` glyph's coverage into a signed distance field
+/// with `spread` pixels of margin on every side. Alpha is 128 at the glyph's
+/// edge and rises towards 255 `spread` pixels inside it, or falls towards 0
+/// as far outside.
+pub fn distance_field(coverage: &[u8], width: u32, height: u32, spread: u32) -> Vec<u8> {
+    let (outer_width, outer_height) = ((width + 2 * spread) as usize, (height + 2 * spread) as usize);
+    let inside: Vec<bool> = (0..outer_width * outer_height)
+        .map(| i | {
+            let (x, y) = ((i % outer_width) as i64 - spread as i64, (i / outer_width) as i64 - spread as i64);
+            let within = (0..width as i64).contains(&x) && (0..height as i64).contains(&y);
+            within && coverage[(y * width as i64 + x) as usize] >= 128
+        })
+        .collect();
+
+    let to_inside = squared_distances(&inside, outer_width, true);
+    let to_outside = squared_distances(&inside, outer_width, false);
+
+    inside
+        .iter()
+        .zip(to_inside.iter().zip(&to_outside))
+        .map(| (&inside, (to_inside, to_outside)) | {
+            // centres of pixels either side of an edge are half a pixel from it
+            let distance = if inside { 0.5 - to_outside.sqrt() } else { to_inside.sqrt() - 0.5 };
+            let value = 0.5 - distance / (2.0 * spread as f64);
+            (value.clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect()
+}
+
+/// Repacks the glyphs of a bitmap atlas, an RGBA image stored top row first,
+/// as distance fields reaching `spread` pixels past their edges, moving the
+/// glyphs in `description` to match.
+pub fn repack(description: &mut FontDescription, image: &TargaImage, spread: u32) -> Atlas {
+    let mut atlas = Atlas::default();
+    let glyphs = description.glyphs.values_mut().chain(description.fallback.as_mut());
+    for glyph in glyphs.filter(| glyph | glyph.width > 0 && glyph.height > 0) {
+        let coverage: Vec<u8> = (glyph.y..(glyph.y + glyph.height))
+            .flat_map(| y | (glyph.x..(glyph.x + glyph.width)).map(move | x | (x, y)))
+            .map(| (x, y) | image.get_pixel(x, y).map_or(0, | pixel | pixel[3]))
+            .collect();
+        let field = distance_field(&coverage, glyph.width, glyph.height, spread);
+        let (outer_width, outer_height) = (glyph.width + 2 * spread, glyph.height + 2 * spread);
+
+        let (x, y) = match atlas.place(outer_width, outer_height) {
+            Some(position) => position,
+            None => {
+                (glyph.width, glyph.height) = (0, 0);
+                continue;
+            },
+        };
+        atlas.write(x, y, outer_width, &field);
+        (glyph.x, glyph.y, glyph.width, glyph.height) = (x, y, outer_width, outer_height);
+        glyph.x_offset -= spread as i32;
+        glyph.y_offset -= spread as i32;
+    }
+    description.scale = atlas.image().1;
+
+    atlas
+}
+
+// The squared distance from each pixel to the nearest one that is or is
+// not `inside`, as `feature` says, by transforming columns then rows.
+fn squared_distances(inside: &[bool], width: usize, feature: bool) -> Vec<f64> {
+    let height = inside.len() / width;
+    let mut distances: Vec<f64> = inside.iter().map(| &pixel | if pixel == feature { 0.0 } else { FAR }).collect();
+
+    let mut column = vec![0.0; height];
+    for x in 0..width {
+        let line: Vec<f64> = (0..height).map(| y | distances[y * width + x]).collect();
+        transform(&line, &mut column);
+        for (y, &distance) in column.iter().enumerate() {
+            distances[y * width + x] = distance;
+        }
+    }
+    let mut row = vec![0.0; width];
+    for line in distances.chunks_mut(width) {
+        transform(line, &mut row);
+        line.copy_from_slice(&row);
+    }
+
+    distances
+}
+
+// Felzenszwalb and Huttenlocher's distance transform of a sampled function,
+// the lower envelope of the parabolas rooted at each sample.
+fn transform(samples: &[f64], distances: &mut [f64]) {
+    let parabola = | q: usize | samples[q] + (q * q) as f64;
+    // roots of the parabolas in the envelope, and where each takes over from the last
+    let mut roots = vec![0; samples.len()];
+    let mut bounds = vec![f64::NEG_INFINITY, f64::INFINITY];
+    let mut k = 0;
+
+    for q in 1..samples.len() {
+        let mut start;
+        loop {
+            start = (parabola(q) - parabola(roots[k])) / (2 * (q - roots[k])) as f64;
+            if start > bounds[k] {
+                break;
+            }
+            k -= 1;
+        }
+        k += 1;
+        roots[k] = q;
+        bounds.truncate(k);
+        bounds.extend([start, f64::INFINITY]);
+    }
+
+    k = 0;
+    for (q, distance) in distances.iter_mut().enumerate() {
+        while bounds[k + 1] < q as f64 {
+            k += 1;
+        }
+        let offset = q as f64 - roots[k] as f64;
+        *distance = offset * offset + samples[roots[k]];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Glyph;
+
+    #[test]
+    fn fields_measure_distance_to_the_edge() {
+        let distances = squared_distances(&[true, false, false, false, false, true], 3, true);
+        assert_eq!(distances, [0.0, 1.0, 1.0, 1.0, 1.0, 0.0]);
+        let distances = squared_distances(&[true, false, false, false, false, false], 3, true);
+        assert_eq!(distances, [0.0, 1.0, 4.0, 1.0, 2.0, 5.0]);
+
+        // a 2 by 2 square, with a margin of 2
+        let field = distance_field(&[255, 255, 255, 0], 2, 2, 2);
+        assert_eq!(field.len(), 6 * 6);
+        let at = | x: usize, y: usize | field[y * 6 + x];
+        assert_eq!(at(2, 2), at(3, 2));
+        assert!(at(2, 2) > 128 && at(3, 3) < 128);
+        assert!(at(1, 2) < 128 && at(0, 2) < at(1, 2));
+        assert_eq!(at(0, 2), 32);
+        assert_eq!(at(5, 5), 0);
+    }
+
+    #[test]
+    fn repacking_reads_glyphs_out_of_the_image() {
+        // a solid 2 by 1 glyph at the right of a 3 by 2 image
+        let mut bytes = vec![0; 4 * 6];
+        bytes[7] = 255;
+        bytes[11] = 255;
+        let image = TargaImage::new(bytes, 3, 2);
+        let glyph = Glyph { x: 1, y: 0, width: 2, height: 1, x_offset: 3, y_offset: 4, advance: 5 };
+        let mut description = FontDescription::default();
+        description.glyphs.insert('-', glyph);
+
+        let atlas = repack(&mut description, &image, 2);
+        let glyph = description.glyph('-');
+        assert_eq!((glyph.width, glyph.height, glyph.x_offset, glyph.y_offset), (6, 5, 1, 2));
+        let (bytes, (width, _)) = atlas.image();
+        let alpha = | x: u32, y: u32 | bytes[(4 * ((glyph.y + y) * width + glyph.x + x) + 3) as usize];
+        assert!(alpha(2, 2) > 128 && alpha(3, 2) > 128);
+        assert!(alpha(1, 2) < 128 && alpha(2, 1) < 128);
+    }
+}
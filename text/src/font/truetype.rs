@@ -0,0 +1,115 @@
+use fontdue::FontSettings;
+use crate::font::atlas::Atlas;
+use crate::font::distance_field::distance_field;
+use crate::font::{FontDescription, Glyph};
+
+/// A TrueType or OpenType font rasterized at one size, glyph by glyph as
+/// they are first asked for, into an atlas that grows to fit them.
+pub struct Outlines {
+    font: fontdue::Font,
+    size: f32,
+    // how far past their edges glyphs are drawn as distance fields, if they are
+    spread: Option<u32>,
+    atlas: Atlas,
+}
+
+impl Outlines {
+    /// Parses a font to rasterize at `size` pixels to the em, as distance
+    /// fields reaching `spread` pixels past the glyphs if given, along with
+    /// its glyph table, which starts empty.
+    pub fn new(bytes: &[u8], size: f32, spread: Option<u32>) -> Result<(Self, FontDescription), String> {
+        let font = fontdue::Font::from_bytes(bytes, FontSettings { scale: size, ..Default::default() })?;
+        let metrics = font.horizontal_line_metrics(size).ok_or("the font is not laid out horizontally")?;
+        if metrics.new_line_size.ceil() < 1.0 {
+            return Err("the font has no line height".to_string());
+        }
+        let atlas = Atlas::default();
+
+        let description = FontDescription {
+            line_height: metrics.new_line_size.ceil() as u32,
+            base: metrics.ascent.round() as u32,
+            scale: atlas.image().1,
+            ..Default::default()
+        };
+
+        Ok((Outlines { font, size, spread, atlas }, description))
+    }
+
+    /// Rasterizes `ch` into the atlas, and adds its glyph and its kerning
+    /// with the glyphs already there to `description`.
+    pub fn add(&mut self, description: &mut FontDescription, ch: char) {
+        let (metrics, mut coverage) = self.font.rasterize(ch, self.size);
+        let (mut width, mut height) = (metrics.width as u32, metrics.height as u32);
+        let mut margin = 0;
+        if let Some(spread) = self.spread.filter(| _ | width > 0 && height > 0) {
+            coverage = distance_field(&coverage, width, height, spread);
+            (width, height) = (width + 2 * spread, height + 2 * spread);
+            margin = spread as i32;
+        }
+        let (x, y, width, height) = match self.atlas.place(width, height) {
+            Some((x, y)) => {
+                self.atlas.write(x, y, width, &coverage);
+                (x, y, width, height)
+            },
+            None => (0, 0, 0, 0),
+        };
+
+        let glyph = Glyph {
+            x,
+            y,
+            width,
+            height,
+            x_offset: metrics.xmin - margin,
+            y_offset: description.base as i32 - metrics.ymin - metrics.height as i32 - margin,
+            advance: metrics.advance_width.round() as i32,
+        };
+        let others: Vec<char> = description.glyphs.keys().copied().chain([ch]).collect();
+        for other in others {
+            for pair in [(other, ch), (ch, other)] {
+                if let Some(amount) = self.font.horizontal_kern(pair.0, pair.1, self.size) {
+                    description.kerning.insert(pair, amount.round() as i32);
+                }
+            }
+        }
+        description.glyphs.insert(ch, glyph);
+        description.scale = self.atlas.image().1;
+    }
+
+    /// The atlas as RGBA bytes, top row first, and its width and height.
+    pub fn image(&self) -> (&[u8], (u32, u32)) {
+        self.atlas.image()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // glyphs for A and V, triangles 500 units wide and 700 high on a 1000 unit em, kerned by -100 units
+    const FONT: &[u8] = include_bytes!("../../res/kerned.ttf");
+
+    #[test]
+    fn glyphs_are_placed_and_kerned_as_added() {
+        let (mut outlines, mut description) = Outlines::new(FONT, 100.0, None).unwrap();
+        assert_eq!((description.line_height, description.base), (100, 80));
+
+        outlines.add(&mut description, 'A');
+        let a = description.glyph('A');
+        assert_eq!((a.width, a.height, a.advance), (50, 70, 60));
+        assert_eq!((a.x_offset, a.y_offset), (5, 10));
+        assert!(description.kerning.is_empty());
+
+        outlines.add(&mut description, 'V');
+        assert_eq!(description.kern('A', 'V'), -10);
+        assert_eq!(description.kern('V', 'A'), 0);
+        let v = description.glyph('V');
+        assert_ne!((v.x, v.y), (a.x, a.y));
+
+        // distance fields take a margin of the spread on every side
+        let (mut outlines, mut description) = Outlines::new(FONT, 100.0, Some(4)).unwrap();
+        outlines.add(&mut description, 'A');
+        let a = description.glyph('A');
+        assert_eq!((a.width, a.height, a.advance), (58, 78, 60));
+        assert_eq!((a.x_offset, a.y_offset), (1, 6));
+    }
+}
@@ -6,19 +6,25 @@ use glutin::context::NotCurrentGlContext;
 use glutin::display::{GetGlDisplay, GlDisplay};
 use glutin::surface::{SurfaceAttributesBuilder, WindowSurface};
 use winit::dpi::PhysicalPosition;
-use winit::event::{Event, WindowEvent};
+use winit::event::{Event, Ime, WindowEvent};
 use winit::event_loop::EventLoop;
-use winit::window::{Icon, WindowBuilder};
+use winit::window::{Icon, Window, WindowBuilder};
 use raw_window_handle::HasRawWindowHandle;
+use std::cell::Cell;
+use std::path::Path;
+use std::rc::Rc;
 
-use simple_targa::read_targa;
+use simple_targa::{read_image_with, Origin, ReadOptions};
 mod text;
-use text::Console;
+use text::{ArgKind, CommandRegistry, Console, ConsoleOptions, Param};
 mod font;
-use font::Font;
+use font::{Effects, Font, FontOptions};
 
 fn main() {
-    let icon = read_targa("res/icon.tga").unwrap();
+    let icon = read_image_with(
+        "res/icon.tga",
+        &ReadOptions { origin: Origin::TopLeft, ..Default::default() },
+    ).unwrap();
     let event_loop = EventLoop::new().unwrap();
     let wb = WindowBuilder::new()
         .with_window_icon(Icon::from_rgba(icon.bytes, icon.width, icon.height).ok())
@@ -32,7 +38,8 @@ fn main() {
             config.next().unwrap()
         }
     ).unwrap();
-    let window = window.unwrap();
+    let window = Rc::new(window.unwrap());
+    window.set_ime_allowed(true);
     let cab = glutin::context::ContextAttributesBuilder::new();
     let not_current_context = unsafe {
         config.display().create_context(&config, &cab.build(Some(window.raw_window_handle()))).unwrap()
@@ -51,11 +58,23 @@ fn main() {
     let current_context = not_current_context.treat_as_possibly_current();
     let display = glium::Display::new(current_context, window_surface).expect("unable to create a new display");
 
-    let mut console = Console::new(&display);
-    let font = Font::new(&display, read_targa("res/font.tga").unwrap());
+    // text [--sdf] [font]
+    let mut args: Vec<_> = std::env::args_os().skip(1).collect();
+    let distance_field = args.iter().position(| arg | arg == "--sdf").map(| i | args.remove(i)).is_some();
+    let font_path = args.pop().unwrap_or("res/font.fnt".into());
+    let font = Rc::new(Font::load(&display, Path::new(&font_path), &FontOptions {
+        distance_field: distance_field.then_some(6),
+    }).unwrap());
+    let mut console = Console::new(&display, &font, &ConsoleOptions {
+        history_file: Some("history.txt".into()),
+        ..Default::default()
+    });
+    let background = Rc::new(Cell::new([0.0f32; 3]));
+    register_commands(console.commands(), &window, &background, &font);
 
     let params = glium::DrawParameters {
         backface_culling: glium::BackfaceCullingMode::CullClockwise,
+        blend: glium::Blend::alpha_blending(),
         ..Default::default()
     };
 
@@ -63,30 +82,147 @@ fn main() {
         if let Event::WindowEvent { event, .. } = event {
             match event {
                 WindowEvent::CloseRequested => elwt.exit(),
+                WindowEvent::Resized(size) => {
+                    display.resize(size.into());
+                    console.resize(size);
+                },
                 WindowEvent::RedrawRequested => {
+                    console.update();
+
                     let mut frame = display.draw();
-                    frame.clear_color(0.0, 0.0, 0.0, 1.0);
+                    let [red, green, blue] = background.get();
+                    frame.clear_color(red, green, blue, 1.0);
 
-                    frame.draw(
-                        console.echo_line().vertices(),
-                        console.echo_line().indices(),
-                        &font.shader,
-                        &uniform! {
-                            translation: [-1.0f32, -1.0],
-                            font: &font.texture,
-                        },
-                        &params,
-                    )
-                    .unwrap();
+                    if let Some((highlight, translation)) = console.selection() {
+                        frame.draw(
+                            highlight.vertices(),
+                            highlight.indices(),
+                            highlight.program(),
+                            &uniform! { translation: translation },
+                            &params,
+                        )
+                        .unwrap();
+                    }
+                    // laying the lines out can rasterize glyphs into the texture, so borrow it after
+                    let lines: Vec<_> = console.lines().collect();
+                    let texture = font.texture();
+                    for (line, translation) in lines {
+                        frame.draw(
+                            line.vertices(),
+                            line.indices(),
+                            &font.shader,
+                            &font.uniforms(&texture, translation),
+                            &params,
+                        )
+                        .unwrap();
+                    }
 
                     frame.finish().unwrap();
                     window.request_redraw();
                 },
                 WindowEvent::KeyboardInput { event, .. } => console.write(event),
+                WindowEvent::Ime(Ime::Commit(text)) => console.commit_text(&text),
                 WindowEvent::ModifiersChanged(mods) => console.set_modifiers(mods.state()),
+                WindowEvent::MouseWheel { delta, .. } => console.scroll(delta),
+                WindowEvent::CursorMoved { position, .. } => console.cursor_moved(position),
+                WindowEvent::MouseInput { state, button, .. } => console.mouse_input(state, button),
                 _ => (),
             }
         }
     })
     .unwrap();
 }
+
+fn register_commands(
+    commands: &mut CommandRegistry,
+    window: &Rc<Window>,
+    background: &Rc<Cell<[f32; 3]>>,
+    font: &Rc<Font>,
+) {
+    commands.register("echo", "show the text again", &[Param::new("text", ArgKind::Text)], | args | {
+        Ok(args.text(0).unwrap().to_string())
+    });
+
+    let colour = Rc::clone(background);
+    commands.register(
+        "background",
+        "set the background colour, each channel from 0 to 1",
+        &[Param::new("red", ArgKind::Float), Param::new("green", ArgKind::Float), Param::new("blue", ArgKind::Float)],
+        move | args | {
+            let channels = [0, 1, 2].map(| i | args.float(i).unwrap() as f32);
+            if channels.iter().any(| channel | !(0.0..=1.0).contains(channel)) {
+                return Err("channels run from 0 to 1".to_string());
+            }
+            colour.set(channels);
+            Ok(String::new())
+        },
+    );
+
+    let moved = Rc::clone(window);
+    commands.register(
+        "move",
+        "move the window's top-left corner to a position on the screen",
+        &[Param::new("x", ArgKind::Int), Param::new("y", ArgKind::Int)],
+        move | args | {
+            let x: i32 = args.int(0).unwrap().try_into().map_err(| _ | "x is out of range")?;
+            let y: i32 = args.int(1).unwrap().try_into().map_err(| _ | "y is out of range")?;
+            moved.set_outer_position(PhysicalPosition::new(x, y));
+            Ok(String::new())
+        },
+    );
+
+    let resized = Rc::clone(window);
+    commands.register(
+        "resizable",
+        "allow or stop resizing the window",
+        &[Param::optional("enabled", ArgKind::Bool)],
+        move | args | {
+            match args.bool(0) {
+                Some(enabled) => resized.set_resizable(enabled),
+                None => return Ok(format!("resizable: {}", resized.is_resizable())),
+            }
+            Ok(String::new())
+        },
+    );
+
+    let outlined = Rc::clone(font);
+    commands.register(
+        "outline",
+        "outline text this many atlas pixels wide, 0 for none, in a colour that defaults to black",
+        &[
+            Param::new("width", ArgKind::Float),
+            Param::optional("red", ArgKind::Float),
+            Param::optional("green", ArgKind::Float),
+            Param::optional("blue", ArgKind::Float),
+        ],
+        move | args | {
+            if !outlined.is_distance_field() {
+                return Err("outlines need a distance field font; start with --sdf".to_string());
+            }
+            let width = args.float(0).unwrap() as f32;
+            let colour = [1, 2, 3].map(| i | args.float(i).unwrap_or(0.0) as f32);
+            outlined.set_effects(Effects { outline_width: width, outline_colour: colour, ..outlined.effects() })?;
+            Ok(String::new())
+        },
+    );
+
+    let shadowed = Rc::clone(font);
+    commands.register(
+        "shadow",
+        "cast a shadow this many atlas pixels right and down, 0 0 for none",
+        &[Param::new("x", ArgKind::Float), Param::new("y", ArgKind::Float), Param::optional("opacity", ArgKind::Float)],
+        move | args | {
+            if !shadowed.is_distance_field() {
+                return Err("shadows need a distance field font; start with --sdf".to_string());
+            }
+            let offset = [args.float(0).unwrap() as f32, args.float(1).unwrap() as f32];
+            let opacity = if offset == [0.0, 0.0] { 0.0 } else { args.float(2).unwrap_or(0.6) as f32 };
+            shadowed.set_effects(Effects {
+                shadow_offset: offset,
+                shadow_colour: [0.0, 0.0, 0.0, opacity],
+                ..shadowed.effects()
+            })?;
+            Ok(String::new())
+        },
+    );
+}
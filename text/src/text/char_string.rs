@@ -1,5 +1,7 @@
+use std::rc::Rc;
 use glium::{backend::Facade, index::PrimitiveType, IndexBuffer, VertexBuffer};
-use crate::text::{MAX_LINE, tex_map};
+use crate::font::{Font, FontDescription};
+use crate::text::MAX_LINE;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct CharVertex {
@@ -9,18 +11,25 @@ pub struct CharVertex {
 
 implement_vertex!(CharVertex, pos, tex);
 
-const HEIGHT: f32 = 0.05f32;
-const WIDTH: f32 = 0.03f32;
-const SPACE: f32 = 0.01f32;
+/// Distance from the bottom of one line of text to the bottom of the next.
+pub const LINE_HEIGHT: f32 = 0.06f32;
+
+/// Screen distance of an atlas pixel, which sets a line of the font `LINE_HEIGHT` tall.
+pub fn scale(description: &FontDescription) -> f32 {
+    LINE_HEIGHT / description.line_height as f32
+}
 
 pub struct CharString {
-    vertex_count: usize,
+    font: Rc<Font>,
+    chars: Vec<char>,
+    // where the pen is after each glyph, in atlas pixels
+    pens: Vec<i32>,
     vertices: VertexBuffer<CharVertex>,
     indices: IndexBuffer<u16>,
 }
 
 impl CharString {
-    pub fn new(facade: &dyn Facade) -> Self {
+    pub fn new(facade: &dyn Facade, font: Rc<Font>) -> Self {
         let indices: Vec<u16> = (0..MAX_LINE as u16)
             .flat_map(| i | {
                 let i = 4 * i;
@@ -29,7 +38,9 @@ impl CharString {
             .collect();
 
         CharString {
-            vertex_count: 0,
+            font,
+            chars: Vec::with_capacity(MAX_LINE),
+            pens: Vec::with_capacity(MAX_LINE),
             vertices: VertexBuffer::dynamic(facade, &[Default::default(); 4 * MAX_LINE]).unwrap(),
             indices: IndexBuffer::immutable(
                 facade,
@@ -39,52 +50,79 @@ impl CharString {
         }
     }
 
-    pub fn vertices(&self) -> glium::vertex::VertexBufferSlice<CharVertex> {
-        self.vertices.slice(0..(4 * self.vertex_count)).unwrap()
+    pub fn vertices(&self) -> glium::vertex::VertexBufferSlice<'_, CharVertex> {
+        self.vertices.slice(0..(4 * self.chars.len())).unwrap()
+    }
+
+    pub fn indices(&self) -> glium::index::IndexBufferSlice<'_, u16> {
+        self.indices.slice(0..(6 * self.chars.len())).unwrap()
     }
 
-    pub fn indices(&self) -> glium::index::IndexBufferSlice<u16> {
-        self.indices.slice(0..(6 * self.vertex_count)).unwrap()
+    /// The characters the glyphs are drawn for.
+    pub fn chars(&self) -> &[char] {
+        &self.chars
     }
 
     pub fn clear(&mut self) {
-        self.vertex_count = 0;
+        self.chars.clear();
+        self.pens.clear();
     }
 
     pub fn append(&mut self, ch: char) {
-
-        let start = (WIDTH + SPACE) * self.vertex_count as f32;
-        let index = 4 * self.vertex_count;
-        let [left, right, bottom, top] = tex_map(ch);
-
-        let vertices = [
-            CharVertex {
-                pos: [start, 0.0],
-                tex: [left, bottom],
-            },
-            CharVertex {
-                pos: [start + WIDTH, 0.0],
-                tex: [right, bottom],
-            },
-            CharVertex {
-                pos: [start, HEIGHT],
-                tex: [left, top],
-            },
-            CharVertex {
-                pos: [start + WIDTH, HEIGHT],
-                tex: [right, top],
-            },
-        ];
-
-        self.vertices.slice_mut(index..(index + 4)).unwrap().write(&vertices);
-
-        self.vertex_count += 1;
+        self.replace_from(self.chars.len(), [ch]);
     }
 
+    /// Rewrites the glyphs from position `index` on with `chars`, dropping any after them.
+    ///
     /// # Panics
     ///
-    /// Panics if called more times than `append`
-    pub fn unappend(&mut self) {
-        self.vertex_count -= 1;
+    /// Panics if `index` is past the end, or the string would grow beyond `MAX_LINE` glyphs
+    pub fn replace_from<I: IntoIterator<Item = char>>(&mut self, index: usize, chars: I) {
+        assert!(index <= self.chars.len(), "glyph {index} is past the end");
+        self.chars.truncate(index);
+        self.pens.truncate(index);
+
+        let chars: Vec<char> = chars.into_iter().collect();
+        let glyphs = self.font.glyphs(chars.iter().copied());
+        let scale = scale(&glyphs);
+        let line_height = glyphs.line_height as i32;
+        let mut vertices = Vec::new();
+        for ch in chars {
+            let previous = self.chars.last().copied();
+            let mut pen = self.pens.last().copied().unwrap_or(0);
+            pen += previous.map_or(0, | previous | glyphs.kern(previous, ch));
+
+            let glyph = glyphs.glyph(ch);
+            let [left, right, bottom, top] = glyph.tex_coords();
+            let x = (pen + glyph.x_offset) as f32 * scale;
+            let y = (line_height - glyph.y_offset - glyph.height as i32) as f32 * scale;
+            let (width, height) = (glyph.width as f32 * scale, glyph.height as f32 * scale);
+
+            vertices.extend([
+                CharVertex {
+                    pos: [x, y],
+                    tex: [left, bottom],
+                },
+                CharVertex {
+                    pos: [x + width, y],
+                    tex: [right, bottom],
+                },
+                CharVertex {
+                    pos: [x, y + height],
+                    tex: [left, top],
+                },
+                CharVertex {
+                    pos: [x + width, y + height],
+                    tex: [right, top],
+                },
+            ]);
+            self.chars.push(ch);
+            self.pens.push(pen + glyph.advance);
+        }
+        assert!(self.chars.len() <= MAX_LINE, "more than {MAX_LINE} glyphs");
+
+        if !vertices.is_empty() {
+            self.vertices.slice_mut((4 * index)..(4 * self.chars.len())).unwrap().write(&vertices);
+        }
     }
 }
@@ -0,0 +1,134 @@
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+pub trait Clipboard {
+    fn copy(&mut self, text: &str) -> io::Result<()>;
+    fn paste(&mut self) -> io::Result<String>;
+}
+
+/// A clipboard only this program sees.
+#[derive(Default)]
+pub struct MemoryClipboard {
+    text: String,
+}
+
+impl Clipboard for MemoryClipboard {
+    fn copy(&mut self, text: &str) -> io::Result<()> {
+        self.text = text.to_string();
+        Ok(())
+    }
+
+    fn paste(&mut self) -> io::Result<String> {
+        Ok(self.text.clone())
+    }
+}
+
+/// The desktop clipboard, through the wl-clipboard tools on Wayland or
+/// xclip or xsel on X11.
+pub struct SystemClipboard {
+    copy: &'static [&'static str],
+    paste: &'static [&'static str],
+}
+
+impl SystemClipboard {
+    /// The first of the tools that suits the session and is installed, if any.
+    pub fn detect() -> Option<Self> {
+        let wayland = [SystemClipboard {
+            copy: &["wl-copy"],
+            paste: &["wl-paste", "--no-newline"],
+        }];
+        let x11 = [
+            SystemClipboard {
+                copy: &["xclip", "-selection", "clipboard", "-in"],
+                paste: &["xclip", "-selection", "clipboard", "-out"],
+            },
+            SystemClipboard {
+                copy: &["xsel", "--clipboard", "--input"],
+                paste: &["xsel", "--clipboard", "--output"],
+            },
+        ];
+        let on_path = | program: &str | {
+            env::var_os("PATH").is_some_and(| path | env::split_paths(&path).any(| dir | dir.join(program).is_file()))
+        };
+
+        let wayland = env::var_os("WAYLAND_DISPLAY").map(| _ | wayland).into_iter().flatten();
+        let x11 = env::var_os("DISPLAY").map(| _ | x11).into_iter().flatten();
+        wayland
+            .chain(x11)
+            .find(| clipboard | on_path(clipboard.copy[0]) && on_path(clipboard.paste[0]))
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn copy(&mut self, text: &str) -> io::Result<()> {
+        // the tools leave a process behind to serve the text, so nothing is read from them
+        let mut child = Command::new(self.copy[0])
+            .args(&self.copy[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        child.stdin.take().expect("stdin is piped").write_all(text.as_bytes())?;
+
+        check(self.copy[0], child.wait()?.success())
+    }
+
+    fn paste(&mut self) -> io::Result<String> {
+        let output = Command::new(self.paste[0]).args(&self.paste[1..]).stderr(Stdio::null()).output()?;
+        check(self.paste[0], output.status.success())?;
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+fn check(program: &str, success: bool) -> io::Result<()> {
+    if success {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{program} failed")))
+    }
+}
+
+/// Splits pasted text into the lines it makes when inserted at `caret`,
+/// with the line's text before the caret joined to the first and its text
+/// after the caret to the last; or refuses it if any would be longer than `max`.
+/// Tabs become spaces and other control characters are dropped.
+pub fn paste_lines(line: &[char], caret: usize, text: &str, max: usize) -> Result<Vec<Vec<char>>, String> {
+    let mut lines: Vec<Vec<char>> = text
+        .split('\n')
+        .map(| part | {
+            part.chars()
+                .map(| ch | if ch == '\t' { ' ' } else { ch })
+                .filter(| ch | !ch.is_control())
+                .collect()
+        })
+        .collect();
+    lines[0].splice(0..0, line[..caret].iter().copied());
+    lines.last_mut().unwrap().extend(&line[caret..]);
+
+    match lines.iter().find(| line | line.len() > max) {
+        Some(_) => Err(format!("pasted text makes a line longer than {max} characters")),
+        None => Ok(lines),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pastes_split_into_lines_that_fit() {
+        let mut clipboard = MemoryClipboard::default();
+        clipboard.copy("b c\r\nd\te").unwrap();
+        let text = clipboard.paste().unwrap();
+
+        let line: Vec<char> = "a f".chars().collect();
+        let lines = paste_lines(&line, 2, &text, 8).unwrap();
+        let lines: Vec<String> = lines.iter().map(| line | line.iter().collect()).collect();
+        assert_eq!(lines, ["a b c", "d ef"]);
+
+        assert!(paste_lines(&line, 2, "1234567", 8).is_err());
+        assert_eq!(paste_lines(&line, 3, "12345", 8).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,302 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgKind {
+    Int,
+    Float,
+    /// `true`, `false`, `on`, `off`, `1` or `0`.
+    Bool,
+    Text,
+}
+
+impl fmt::Display for ArgKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ArgKind::Int => "integer",
+            ArgKind::Float => "number",
+            ArgKind::Bool => "boolean",
+            ArgKind::Text => "text",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Text(String),
+}
+
+impl Arg {
+    fn parse(token: &str, param: &Param) -> Result<Arg, String> {
+        let arg = match param.kind {
+            ArgKind::Int => token.parse().ok().map(Arg::Int),
+            ArgKind::Float => token.parse().ok().filter(| value: &f64 | value.is_finite()).map(Arg::Float),
+            ArgKind::Bool => match token {
+                "true" | "on" | "1" => Some(Arg::Bool(true)),
+                "false" | "off" | "0" => Some(Arg::Bool(false)),
+                _ => None,
+            },
+            ArgKind::Text => Some(Arg::Text(token.to_string())),
+        };
+
+        arg.ok_or(format!("`{token}` is not a valid {} for <{}>", param.kind, param.name))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Param {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    /// Optional parameters may only follow the required ones.
+    pub optional: bool,
+}
+
+impl Param {
+    pub fn new(name: &'static str, kind: ArgKind) -> Self {
+        Param { name, kind, optional: false }
+    }
+
+    pub fn optional(name: &'static str, kind: ArgKind) -> Self {
+        Param { name, kind, optional: true }
+    }
+}
+
+/// Arguments checked against a command's parameters. The accessors give
+/// `None` for an optional argument that was left out, or one of another kind.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Args(Vec<Arg>);
+
+impl Args {
+    pub fn int(&self, index: usize) -> Option<i64> {
+        match self.0.get(index) {
+            Some(&Arg::Int(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn float(&self, index: usize) -> Option<f64> {
+        match self.0.get(index) {
+            Some(&Arg::Float(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn bool(&self, index: usize) -> Option<bool> {
+        match self.0.get(index) {
+            Some(&Arg::Bool(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, index: usize) -> Option<&str> {
+        match self.0.get(index) {
+            Some(Arg::Text(value)) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Runs a command, giving the text to show for it or an error message.
+pub type Handler = Box<dyn FnMut(&Args) -> Result<String, String>>;
+
+struct Command {
+    name: String,
+    description: String,
+    params: Vec<Param>,
+    handler: Handler,
+}
+
+impl Command {
+    fn usage(&self) -> String {
+        let mut usage = self.name.clone();
+        for param in &self.params {
+            let (open, close) = if param.optional { ('[', ']') } else { ('<', '>') };
+            usage += &format!(" {open}{}:{}{close}", param.name, param.kind);
+        }
+
+        usage
+    }
+}
+
+/// Named commands typed into the console, along with a built-in `help`.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        CommandRegistry::default()
+    }
+
+    /// Adds a command, replacing any of the same name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is `help` or not a single word, or a required parameter follows an optional one
+    pub fn register<F>(&mut self, name: &str, description: &str, params: &[Param], handler: F)
+    where
+        F: FnMut(&Args) -> Result<String, String> + 'static,
+    {
+        assert!(name != "help", "`help` is built in");
+        assert!(!name.is_empty() && !name.contains(char::is_whitespace), "bad command name `{name}`");
+        assert!(
+            params.windows(2).all(| pair | !pair[0].optional || pair[1].optional),
+            "required parameter after an optional one in `{name}`",
+        );
+
+        self.commands.retain(| command | command.name != name);
+        self.commands.push(Command {
+            name: name.to_string(),
+            description: description.to_string(),
+            params: params.to_vec(),
+            handler: Box::new(handler),
+        });
+        self.commands.sort_by(| a, b | a.name.cmp(&b.name));
+    }
+
+    /// Runs a typed line, giving the lines of text to show for it.
+    /// Blank lines run nothing.
+    pub fn run(&mut self, line: &str) -> Result<Vec<String>, String> {
+        let tokens = tokenize(line)?;
+        let Some((name, tokens)) = tokens.split_first() else {
+            return Ok(Vec::new());
+        };
+        if name == "help" {
+            return self.help(tokens);
+        }
+
+        let command = self.commands
+            .iter_mut()
+            .find(| command | command.name == *name)
+            .ok_or(format!("unknown command `{name}`; type `help` for a list"))?;
+        let required = command.params.iter().filter(| param | !param.optional).count();
+        if tokens.len() < required || tokens.len() > command.params.len() {
+            return Err(format!("usage: {}", command.usage()));
+        }
+        let args = tokens
+            .iter()
+            .zip(&command.params)
+            .map(| (token, param) | Arg::parse(token, param))
+            .collect::<Result<_, _>>()?;
+
+        let output = (command.handler)(&Args(args))?;
+
+        Ok(output.lines().map(str::to_string).collect())
+    }
+
+    fn help(&self, tokens: &[String]) -> Result<Vec<String>, String> {
+        match tokens {
+            [] => {
+                let mut lines = vec!["commands:".to_string()];
+                lines.push("  help [command:text]".to_string());
+                lines.extend(self.commands.iter().map(| command | format!("  {}", command.usage())));
+                Ok(lines)
+            },
+            [name] => {
+                let command = self.commands
+                    .iter()
+                    .find(| command | command.name == *name)
+                    .ok_or(format!("unknown command `{name}`"))?;
+                Ok(vec![format!("usage: {}", command.usage()), command.description.clone()])
+            },
+            _ => Err("usage: help [command:text]".to_string()),
+        }
+    }
+}
+
+/// Splits a line into words at whitespace. Quotes keep whitespace in a word:
+/// within single quotes everything is literal, while within double quotes
+/// and outside quotes a backslash escapes the next character, and `\n` and
+/// `\t` stand for a newline and a tab.
+pub fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    // the word being read, if any, which quotes can start even when empty
+    let mut token: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => token.get_or_insert_with(String::new).push(ch),
+            (_, '\\') => {
+                let escaped = match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some(escaped) => escaped,
+                    None => return Err("line ends with an unfinished escape".to_string()),
+                };
+                token.get_or_insert_with(String::new).push(escaped);
+            },
+            (Some(_), _) => token.get_or_insert_with(String::new).push(ch),
+            (None, '\'' | '"') => {
+                quote = Some(ch);
+                token.get_or_insert_with(String::new);
+            },
+            (None, _) if ch.is_whitespace() => tokens.extend(token.take()),
+            (None, _) => token.get_or_insert_with(String::new).push(ch),
+        }
+    }
+    if let Some(quote) = quote {
+        return Err(format!("missing closing {quote}"));
+    }
+    tokens.extend(token);
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn quotes_and_escapes_group_words() {
+        assert_eq!(tokenize("  set   level 64 ").unwrap(), ["set", "level", "64"]);
+        assert_eq!(tokenize(r#"echo "a  b" 'c \d' e\ f"#).unwrap(), ["echo", "a  b", r"c \d", "e f"]);
+        assert_eq!(tokenize(r#"say "\"hi\"\n" '' x"y"z"#).unwrap(), ["say", "\"hi\"\n", "", "xyz"]);
+        assert!(tokenize("echo \"open").is_err());
+        assert!(tokenize("echo end\\").is_err());
+    }
+
+    #[test]
+    fn commands_check_their_arguments() {
+        let level = Rc::new(Cell::new(0));
+        let mut registry = CommandRegistry::new();
+        let set = Rc::clone(&level);
+        registry.register(
+            "tess",
+            "set the tessellation level",
+            &[Param::new("level", ArgKind::Int), Param::optional("verbose", ArgKind::Bool)],
+            move | args | {
+                set.set(args.int(0).unwrap());
+                Ok(if args.bool(1) == Some(true) { format!("level {}", set.get()) } else { String::new() })
+            },
+        );
+
+        assert_eq!(registry.run("tess 16").unwrap(), Vec::<String>::new());
+        assert_eq!(level.get(), 16);
+        assert_eq!(registry.run("tess 32 on").unwrap(), ["level 32"]);
+        assert_eq!(registry.run("   ").unwrap(), Vec::<String>::new());
+
+        assert_eq!(registry.run("tess").unwrap_err(), "usage: tess <level:integer> [verbose:boolean]");
+        assert_eq!(registry.run("tess 1 2 3").unwrap_err(), "usage: tess <level:integer> [verbose:boolean]");
+        assert_eq!(registry.run("tess high").unwrap_err(), "`high` is not a valid integer for <level>");
+        assert!(registry.run("fxaa on").unwrap_err().starts_with("unknown command `fxaa`"));
+        assert_eq!(level.get(), 32);
+
+        let float = Param::new("x", ArgKind::Float);
+        assert!(matches!(Arg::parse("-0.5", &float), Ok(Arg::Float(-0.5))));
+        assert!(Arg::parse("NaN", &float).is_err());
+        assert!(Arg::parse("inf", &float).is_err());
+
+        assert_eq!(registry.run("help").unwrap().len(), 3);
+        assert_eq!(registry.run("help tess").unwrap()[1], "set the tessellation level");
+    }
+}
@@ -1,113 +1,245 @@
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use glium::glutin::surface::WindowSurface;
-use winit::event::{ElementState, KeyEvent};
-use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
-use crate::text::{char_string::CharString, key_map, MAX_LINE};
-
-#[derive(Clone, Copy, Debug)]
-struct Key {
-    keycode: KeyCode,
-    modifiers: ModifiersState,
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta};
+use winit::keyboard::ModifiersState;
+use crate::font::Font;
+use crate::text::char_string::{self, CharString, LINE_HEIGHT};
+use crate::text::clipboard::{Clipboard, MemoryClipboard, SystemClipboard};
+use crate::text::command::CommandRegistry;
+use crate::text::editor::Editor;
+use crate::text::highlight::Highlight;
+use crate::text::keyboard::{KeyInput, KeyRepeat, RepeatRate};
+
+/// Committed lines that fit above the input line.
+const ROWS: usize = (2.0 / LINE_HEIGHT) as usize - 1;
+/// How long the caret stays shown, and then hidden.
+const BLINK: Duration = Duration::from_millis(530);
+
+#[derive(Clone, Debug)]
+pub struct ConsoleOptions {
+    /// Committed lines kept for scrolling back through; older ones are dropped.
+    pub line_limit: usize,
+    /// Submitted lines kept for recalling with Up and Down.
+    pub history_limit: usize,
+    /// File to load the history from and save it to, if any.
+    pub history_file: Option<PathBuf>,
+    /// Timing of the repeats typed while a key is held, for platforms that
+    /// do not repeat keys themselves, or `None` to rely on the OS, as most do.
+    pub repeat: Option<RepeatRate>,
 }
 
-impl From<KeyEvent> for Key {
-    fn from(event: KeyEvent) -> Self {
-        match event {
-            KeyEvent {
-                physical_key: PhysicalKey::Code(keycode),
-                ..
-            } => {
-                Key {
-                    keycode,
-                    modifiers: ModifiersState::empty(),
-                }
-            },
-            _ => panic!("Unexpected event"),
+impl Default for ConsoleOptions {
+    fn default() -> Self {
+        ConsoleOptions {
+            line_limit: 1000,
+            history_limit: 500,
+            history_file: None,
+            repeat: None,
         }
     }
 }
 
 pub struct Console {
-    history: Vec<Key>,
-    modifiers: ModifiersState,
+    editor: Editor,
+    repeat: KeyRepeat,
+    font: Rc<Font>,
     echo_line: CharString,
+    // column of `echo_line` the caret is drawn at
+    caret: usize,
+    highlight: Highlight,
+    caret_glyph: CharString,
+    caret_moved: Instant,
+    rows: Vec<CharString>,
+    // for turning touchpad scrolling into lines
+    line_pixels: f64,
+    wheel: f64,
+    size: (u32, u32),
+    cursor: PhysicalPosition<f64>,
+    // the column a drag with the left button started at
+    dragging: Option<usize>,
 }
 
 impl Console {
-    pub fn new(display: &glium::Display<WindowSurface>) -> Self {
-        let echo_line = CharString::new(display);
+    /// A console drawing its text with the glyphs of `font`.
+    pub fn new(display: &glium::Display<WindowSurface>, font: &Rc<Font>, options: &ConsoleOptions) -> Self {
+        let echo_line = CharString::new(display, Rc::clone(font));
+        let rows = (0..ROWS).map(| _ | CharString::new(display, Rc::clone(font))).collect();
+        let mut caret_glyph = CharString::new(display, Rc::clone(font));
+        caret_glyph.append('_');
+
+        let (width, height) = display.get_framebuffer_dimensions();
+        let clipboard: Box<dyn Clipboard> = match SystemClipboard::detect() {
+            Some(clipboard) => Box::new(clipboard),
+            None => Box::new(MemoryClipboard::default()),
+        };
+
+        let mut console = Console {
+            editor: Editor::new(options, ROWS, clipboard),
+            repeat: KeyRepeat::new(options.repeat),
+            font: Rc::clone(font),
+            echo_line,
+            caret: 0,
+            highlight: Highlight::new(display),
+            caret_glyph,
+            caret_moved: Instant::now(),
+            rows,
+            line_pixels: 0.0,
+            wheel: 0.0,
+            size: (0, 0),
+            cursor: PhysicalPosition::new(0.0, 0.0),
+            dragging: None,
+        };
+        console.resize(PhysicalSize::new(width, height));
+        console.sync();
+
+        console
+    }
 
-        let modifiers = ModifiersState::empty();
+    /// Keeps the mouse in step with the window once it is resized.
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.size = size.into();
+        self.line_pixels = size.height as f64 * LINE_HEIGHT as f64 / 2.0;
+    }
 
-        Console {
-            history: Vec::with_capacity(MAX_LINE),
-            modifiers,
-            echo_line,
-        }
+    /// Every line on screen with the translation that puts it in place:
+    /// the input line, the scrollback from the bottom up, and the caret
+    /// while it is blinked on.
+    pub fn lines(&self) -> impl Iterator<Item = (&CharString, [f32; 2])> {
+        let blinks = self.caret_moved.elapsed().as_millis() / BLINK.as_millis();
+        let position = [-1.0 + self.column_x(self.caret), -1.0];
+        let caret = blinks.is_multiple_of(2).then_some((&self.caret_glyph, position));
+
+        std::iter::once(&self.echo_line)
+            .chain(&self.rows)
+            .enumerate()
+            .map(| (i, line) | (line, [-1.0, -1.0 + i as f32 * LINE_HEIGHT]))
+            .chain(caret)
     }
 
-    pub fn echo_line(&self) -> &CharString {
-        &self.echo_line
+    /// The quad to draw behind the input line's selection, if anything is selected.
+    pub fn selection(&self) -> Option<(&Highlight, [f32; 2])> {
+        self.editor.selection().map(| _ | (&self.highlight, [-1.0, -1.0]))
     }
 
-    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
-        self.modifiers = modifiers;
+    pub fn cursor_moved(&mut self, position: PhysicalPosition<f64>) {
+        self.cursor = position;
+        if let Some(anchor) = self.dragging {
+            self.editor.select(anchor, self.column_at_cursor());
+            self.sync();
+        }
     }
 
-    pub fn write(&mut self, event: KeyEvent) {
-        match event {
-            KeyEvent {
-                state: ElementState::Released,
-                physical_key: PhysicalKey::Code(KeyCode::Enter),
-                ..
-            } => self.flush(),
-
-            KeyEvent {
-                state: ElementState::Released,
-                physical_key: PhysicalKey::Code(KeyCode::Escape),
-                ..
-            } => {
-                self.history.clear();
-                self.echo_line.clear();
+    /// Selects by dragging across the input line with the left button.
+    pub fn mouse_input(&mut self, state: ElementState, button: MouseButton) {
+        // the input line is the bottom row
+        let on_input = self.cursor.y >= self.size.1 as f64 - self.line_pixels;
+
+        match (state, button) {
+            (ElementState::Pressed, MouseButton::Left) if on_input && !self.editor.is_searching() => {
+                let column = self.column_at_cursor();
+                self.dragging = Some(column);
+                self.editor.select(column, column);
+                self.sync();
             },
+            (ElementState::Released, MouseButton::Left) => self.dragging = None,
+            _ => (),
+        }
+    }
 
-            KeyEvent {
-                state: ElementState::Released,
-                physical_key: PhysicalKey::Code(KeyCode::Backspace),
-                ..
-            } => if !self.history.is_empty() {
-                self.history.pop();
-                self.echo_line.unappend();
-            },
+    /// The commands that committed lines run.
+    pub fn commands(&mut self) -> &mut CommandRegistry {
+        self.editor.commands()
+    }
 
-            KeyEvent {
-                state: ElementState::Released,
-                ..
-            } => {
-                if self.history.len() == MAX_LINE {
-                    self.flush();
-                }
-                let mut key: Key = event.into();
-                key.modifiers = self.modifiers;
-
-                let key_char = key_map(&key.keycode, &key.modifiers);
-                if key_char != '\0' {
-                    self.history.push(key);
-                    self.echo_line.append(key_char);
-                }
-            },
+    pub fn scroll(&mut self, delta: MouseScrollDelta) {
+        self.wheel += match delta {
+            MouseScrollDelta::LineDelta(_, lines) => lines as f64,
+            MouseScrollDelta::PixelDelta(position) => position.y / self.line_pixels,
+        };
+        let lines = self.wheel.trunc();
+        self.wheel -= lines;
+
+        if lines != 0.0 {
+            self.editor.scroll(lines as isize);
+            self.sync();
+        }
+    }
 
-            _ => (),
+    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.editor.set_modifiers(modifiers);
+    }
+
+    /// Types text committed by an input method.
+    pub fn commit_text(&mut self, text: &str) {
+        self.editor.commit_text(text);
+        self.sync();
+    }
+
+    pub fn write(&mut self, event: KeyEvent) {
+        let input = KeyInput::from(event);
+        if self.repeat.accept(&input, Instant::now()) {
+            self.editor.key(input);
+            self.sync();
         }
     }
 
-    fn flush(&mut self) {
-        for key in &self.history {
-            print!("{}", key_map(&key.keycode, &key.modifiers));
+    /// Types the repeats due for a held key, on platforms that leave repeating to us.
+    pub fn update(&mut self) {
+        for input in self.repeat.due(Instant::now()) {
+            self.editor.key(input);
+            self.sync();
         }
+    }
 
-        self.history.clear();
-        self.echo_line.clear();
-        println!();
+    // Redraws what the editor has changed: the input line from its first
+    // changed glyph on, the caret, restarting its blink so it is seen straight
+    // away, the selection, and the rows above.
+    fn sync(&mut self) {
+        let (shown, caret) = self.editor.shown();
+        let unchanged = self.echo_line.chars().iter().zip(&shown).take_while(| (old, new) | old == new).count();
+        let edited = unchanged < shown.len() || unchanged < self.echo_line.chars().len();
+        if edited {
+            self.echo_line.replace_from(unchanged, shown[unchanged..].iter().copied());
+        }
+        if edited || caret != self.caret {
+            self.caret = caret;
+            self.caret_moved = Instant::now();
+        }
+        if let Some(range) = self.editor.selection() {
+            self.highlight.set_span(self.column_x(range.start), self.column_x(range.end));
+        }
+        if self.editor.take_rows_changed() {
+            self.refresh_rows();
+        }
     }
-}
 
+    // How far along the input line, as it is shown, column `column` starts.
+    fn column_x(&self, column: usize) -> f32 {
+        let before: String = self.echo_line.chars()[..column].iter().collect();
+        let glyphs = self.font.glyphs(before.chars());
+
+        char_string::scale(&glyphs) * glyphs.measure(&before).0
+    }
+
+    fn column_at_cursor(&self) -> usize {
+        let x = (self.cursor.x / self.size.0 as f64 * 2.0) as f32;
+
+        (0..=self.echo_line.chars().len())
+            .min_by(| &a, &b | (self.column_x(a) - x).abs().total_cmp(&(self.column_x(b) - x).abs()))
+            .unwrap_or(0)
+    }
+
+    // Rewrites the rows above the input line from the part of the scrollback in view.
+    fn refresh_rows(&mut self) {
+        let mut visible = self.editor.visible();
+        for row in &mut self.rows {
+            row.clear();
+            for ch in visible.next().unwrap_or_default().chars() {
+                row.append(ch);
+            }
+        }
+    }
+}
@@ -0,0 +1,604 @@
+use std::ops::Range;
+use winit::keyboard::{Key, KeyCode, ModifiersState, PhysicalKey};
+use crate::text::clipboard::{paste_lines, Clipboard};
+use crate::text::command::CommandRegistry;
+use crate::text::console::ConsoleOptions;
+use crate::text::history::History;
+use crate::text::keyboard::{is_modifier, KeyInput};
+use crate::text::scrollback::Scrollback;
+use crate::text::{key_map, MAX_LINE};
+
+/// The text that starts the input line while searching the history.
+const SEARCH_PROMPT: &str = "search: ";
+
+// What Ctrl+R is looking for, and the history entry last found.
+struct Search {
+    query: String,
+    found: Option<usize>,
+}
+
+/// What the console does with keys, without drawing anything: the input
+/// line with its caret and selection, the history and searching it, and
+/// the committed lines shown above.
+pub struct Editor {
+    input: Vec<char>,
+    modifiers: ModifiersState,
+    // position in `input` that typing inserts at
+    caret: usize,
+    // the other end of the selection from the caret, if any
+    anchor: Option<usize>,
+    clipboard: Box<dyn Clipboard>,
+    commands: CommandRegistry,
+    history: History,
+    search: Option<Search>,
+    scrollback: Scrollback,
+    // committed lines in view
+    rows: usize,
+    // whether the lines in view have changed since they were last drawn
+    rows_changed: bool,
+}
+
+impl Editor {
+    /// An editor showing `rows` committed lines at a time.
+    pub fn new(options: &ConsoleOptions, rows: usize, clipboard: Box<dyn Clipboard>) -> Self {
+        let mut scrollback = Scrollback::new(options.line_limit, rows, MAX_LINE);
+        let history = match &options.history_file {
+            Some(path) => History::load(path, options.history_limit).unwrap_or_else(| error | {
+                scrollback.push(format!("error: cannot load history from {}: {error}", path.display()));
+                History::new(options.history_limit)
+            }),
+            None => History::new(options.history_limit),
+        };
+
+        Editor {
+            input: Vec::with_capacity(MAX_LINE),
+            modifiers: ModifiersState::empty(),
+            caret: 0,
+            anchor: None,
+            clipboard,
+            commands: CommandRegistry::new(),
+            history,
+            search: None,
+            scrollback,
+            rows,
+            rows_changed: true,
+        }
+    }
+
+    /// The input line as it is shown, which is the search while there is
+    /// one, and the column the caret is at in it.
+    pub fn shown(&self) -> (Vec<char>, usize) {
+        match &self.search {
+            Some(search) => {
+                let found = search.found.and_then(| index | self.history.get(index)).unwrap_or_default();
+                search_line(&search.query, found)
+            },
+            None => (self.input.clone(), self.caret),
+        }
+    }
+
+    /// The selected columns of the input line, if any are.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        let range = anchor.min(self.caret)..anchor.max(self.caret);
+
+        Some(range).filter(| range | !range.is_empty())
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.search.is_some()
+    }
+
+    /// Selects from column `anchor` to column `caret` of the input line,
+    /// unless the history is being searched.
+    pub fn select(&mut self, anchor: usize, caret: usize) {
+        if self.search.is_none() {
+            self.anchor = Some(anchor.min(self.input.len()));
+            self.caret = caret.min(self.input.len());
+        }
+    }
+
+    /// Up to `rows` committed lines in view, from the bottom up.
+    pub fn visible(&self) -> impl Iterator<Item = &str> {
+        self.scrollback.visible()
+    }
+
+    /// Whether the lines in view have changed since this was last asked.
+    pub fn take_rows_changed(&mut self) -> bool {
+        std::mem::take(&mut self.rows_changed)
+    }
+
+    /// The commands that committed lines run.
+    pub fn commands(&mut self) -> &mut CommandRegistry {
+        &mut self.commands
+    }
+
+    /// Moves the view `lines` further back through the committed lines, or forward when negative.
+    pub fn scroll(&mut self, lines: isize) {
+        self.scrollback.scroll(lines);
+        self.rows_changed = true;
+    }
+
+    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    /// Types text committed by an input method.
+    pub fn commit_text(&mut self, text: &str) {
+        self.insert(text.chars().filter(| ch | !ch.is_control()));
+    }
+
+    /// Acts on a key press.
+    pub fn key(&mut self, input: KeyInput) {
+        if self.search.is_some() && self.search_key(&input) {
+            return;
+        }
+
+        match input {
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::Enter),
+                ..
+            } => self.flush(),
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::Escape),
+                ..
+            } => self.set_input(""),
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::ArrowUp),
+                ..
+            } => {
+                let typed: String = self.input.iter().collect();
+                if let Some(line) = self.history.previous(&typed).map(str::to_string) {
+                    self.set_input(&line);
+                }
+            },
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::ArrowDown),
+                ..
+            } => {
+                if let Some(line) = self.history.next() {
+                    self.set_input(&line);
+                }
+            },
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::KeyR),
+                ..
+            } if self.modifiers.control_key() => {
+                self.anchor = None;
+                self.search = Some(Search { query: String::new(), found: None });
+            },
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(code @ (KeyCode::KeyC | KeyCode::KeyX)),
+                ..
+            } if self.modifiers.control_key() => {
+                let Some(range) = self.selection() else {
+                    return;
+                };
+                let selected: String = self.input[range].iter().collect();
+                match self.clipboard.copy(&selected) {
+                    Ok(()) if code == KeyCode::KeyX => {
+                        self.delete_selection();
+                    },
+                    Ok(()) => (),
+                    Err(error) => self.report(format!("cannot copy: {error}")),
+                }
+            },
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::KeyV),
+                ..
+            } if self.modifiers.control_key() => match self.clipboard.paste() {
+                Ok(text) => self.paste(&text),
+                Err(error) => self.report(format!("cannot paste: {error}")),
+            },
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::PageUp),
+                ..
+            } => self.scroll(self.rows as isize - 1),
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::PageDown),
+                ..
+            } => self.scroll(1 - self.rows as isize),
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::ArrowLeft),
+                ..
+            } => {
+                let caret = if self.modifiers.control_key() {
+                    previous_word(&self.input, self.caret)
+                } else {
+                    self.caret.saturating_sub(1)
+                };
+                self.select_to(caret);
+            },
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::ArrowRight),
+                ..
+            } => {
+                let caret = if self.modifiers.control_key() {
+                    next_word(&self.input, self.caret)
+                } else {
+                    self.input.len().min(self.caret + 1)
+                };
+                self.select_to(caret);
+            },
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::Home),
+                ..
+            } => self.select_to(0),
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::End),
+                ..
+            } => self.select_to(self.input.len()),
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::Delete),
+                ..
+            } => {
+                if !self.delete_selection() && self.caret < self.input.len() {
+                    self.input.remove(self.caret);
+                }
+            },
+
+            KeyInput {
+                physical_key: PhysicalKey::Code(KeyCode::Backspace),
+                ..
+            } => {
+                if self.delete_selection() {
+                    return;
+                }
+                let start = if self.modifiers.control_key() {
+                    previous_word(&self.input, self.caret)
+                } else {
+                    self.caret.saturating_sub(1)
+                };
+                self.input.drain(start..self.caret);
+                self.caret = start;
+            },
+
+            KeyInput {
+                physical_key,
+                logical_key,
+                text,
+                ..
+            } => {
+                let text = typed_text(text.as_deref(), &logical_key, physical_key, self.modifiers);
+                self.insert(text.chars());
+            },
+        }
+    }
+
+    // Handles a key while searching the history, giving whether that used it up.
+    // Keys that do not change the search end it with the line found, and then
+    // act on that line as usual.
+    fn search_key(&mut self, input: &KeyInput) -> bool {
+        let Some(search) = &mut self.search else {
+            return false;
+        };
+        let text = typed_text(input.text.as_deref(), &input.logical_key, input.physical_key, self.modifiers);
+
+        match input.physical_key {
+            PhysicalKey::Code(KeyCode::KeyR) if self.modifiers.control_key() => {
+                let before = search.found.unwrap_or(self.history.len());
+                search.found = self.history.search(&search.query, before).or(search.found);
+            },
+            PhysicalKey::Code(KeyCode::Escape) => {
+                self.search = None;
+                let typed: String = self.input.iter().collect();
+                self.set_input(&typed);
+            },
+            PhysicalKey::Code(KeyCode::Backspace) => {
+                search.query.pop();
+                search.found = self.history.search(&search.query, self.history.len());
+            },
+            // held for the next key, which may type into the query
+            _ if is_modifier(&input.logical_key) => (),
+            _ if !text.is_empty() => {
+                search.query += &text;
+                search.found = self.history.search(&search.query, self.history.len());
+            },
+            _ => {
+                let found = search.found.and_then(| index | self.history.get(index));
+                let line = found.map_or_else(|| self.input.iter().collect(), str::to_string);
+                self.search = None;
+                self.set_input(&line);
+                return false;
+            },
+        }
+
+        true
+    }
+
+    // Replaces the input line, leaving the caret at its end.
+    fn set_input(&mut self, line: &str) {
+        self.anchor = None;
+        self.input = line.chars().take(MAX_LINE).collect();
+        self.caret = self.input.len();
+    }
+
+    // Inserts at the caret in place of any selection, dropping what does not fit.
+    fn insert<I: Iterator<Item = char>>(&mut self, chars: I) {
+        let mut chars = chars.peekable();
+        if chars.peek().is_none() {
+            return;
+        }
+        self.delete_selection();
+        for ch in chars.take(MAX_LINE - self.input.len()) {
+            self.input.insert(self.caret, ch);
+            self.caret += 1;
+        }
+    }
+
+    // Inserts pasted text like `insert`, committing the line at each line break,
+    // unless that would make a line too long.
+    fn paste(&mut self, text: &str) {
+        let (mut line, mut caret) = (self.input.clone(), self.caret);
+        if let Some(range) = self.selection() {
+            caret = range.start;
+            line.drain(range);
+        }
+        let after_caret = line.len() - caret;
+
+        match paste_lines(&line, caret, text, MAX_LINE) {
+            Ok(lines) => {
+                let (last, complete) = lines.split_last().expect("splitting text gives a line");
+                for line in complete {
+                    self.input = line.clone();
+                    self.flush();
+                }
+                self.anchor = None;
+                self.input = last.clone();
+                self.caret = self.input.len() - after_caret;
+            },
+            Err(message) => self.report(message),
+        }
+    }
+
+    // Removes the selected text, giving whether there was any.
+    fn delete_selection(&mut self) -> bool {
+        let selected = self.selection();
+        self.anchor = None;
+        let Some(range) = selected else {
+            return false;
+        };
+        self.input.drain(range.clone());
+        self.caret = range.start;
+
+        true
+    }
+
+    // Moves the caret, extending the selection while Shift is held and dropping it otherwise.
+    fn select_to(&mut self, caret: usize) {
+        if self.modifiers.shift_key() {
+            self.anchor.get_or_insert(self.caret);
+        } else {
+            self.anchor = None;
+        }
+        self.caret = caret;
+    }
+
+    // Shows an error below what has been committed.
+    fn report(&mut self, message: String) {
+        self.scrollback.push(format!("error: {message}"));
+        self.scrollback.scroll_to_bottom();
+        self.rows_changed = true;
+    }
+
+    // Commits the input line, running it as a command and showing what that gives.
+    fn flush(&mut self) {
+        let line: String = self.input.iter().collect();
+
+        self.anchor = None;
+        self.input.clear();
+        self.caret = 0;
+        self.scrollback.push(format!("> {line}"));
+        if let Err(error) = self.history.push(&line) {
+            self.scrollback.push(format!("error: cannot save history: {error}"));
+        }
+        match self.commands.run(&line) {
+            Ok(output) => output.into_iter().for_each(| line | self.scrollback.push(line)),
+            Err(message) => self.scrollback.push(format!("error: {message}")),
+        }
+        self.scrollback.scroll_to_bottom();
+        self.rows_changed = true;
+    }
+}
+
+// The input line showing a search for `query` that has found `found`, cut
+// to fit, and the column just after the query to show the caret at.
+fn search_line(query: &str, found: &str) -> (Vec<char>, usize) {
+    let shown: Vec<char> = format!("{SEARCH_PROMPT}{query} -> {found}").chars().take(MAX_LINE).collect();
+    let caret = SEARCH_PROMPT.chars().count() + query.chars().count();
+
+    (shown, caret.min(MAX_LINE))
+}
+
+// What a key types: the text the OS gives, which accounts for the layout, Caps Lock and dead keys, or failing that
+// the character of the logical key, or failing that a US layout guess. Ctrl chords type nothing.
+fn typed_text(
+    text: Option<&str>,
+    logical_key: &Key,
+    physical_key: PhysicalKey,
+    modifiers: ModifiersState,
+) -> String {
+    let printable = | text: &str | text.chars().filter(| ch | !ch.is_control()).collect::<String>();
+
+    match (text.map(printable), logical_key, physical_key) {
+        _ if modifiers.control_key() => String::new(),
+        (Some(text), _, _) if !text.is_empty() => text,
+        // the character comes with the next key
+        (_, Key::Dead(_), _) => String::new(),
+        (_, Key::Character(text), _) if !printable(text).is_empty() => printable(text),
+        (_, _, PhysicalKey::Code(keycode)) => {
+            Some(key_map(&keycode, &modifiers)).filter(| ch | !ch.is_control()).into_iter().collect()
+        },
+        _ => String::new(),
+    }
+}
+
+// Where Ctrl+Left stops: the start of the word before `caret`.
+fn previous_word(line: &[char], caret: usize) -> usize {
+    let spaces = line[..caret].iter().rev().take_while(| ch | ch.is_whitespace()).count();
+    let word = line[..(caret - spaces)].iter().rev().take_while(| ch | !ch.is_whitespace()).count();
+
+    caret - spaces - word
+}
+
+// Where Ctrl+Right stops: the start of the word after `caret`, or the end of the line.
+fn next_word(line: &[char], caret: usize) -> usize {
+    let word = line[caret..].iter().take_while(| ch | !ch.is_whitespace()).count();
+    let spaces = line[(caret + word)..].iter().take_while(| ch | ch.is_whitespace()).count();
+
+    caret + word + spaces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::ElementState;
+    use winit::keyboard::{NamedKey, NativeKey, SmolStr};
+    use crate::text::clipboard::MemoryClipboard;
+
+    fn editor() -> Editor {
+        Editor::new(&ConsoleOptions::default(), 3, Box::new(MemoryClipboard::default()))
+    }
+
+    // Presses a key as winit reports it, once `modifiers` are held.
+    fn press(editor: &mut Editor, code: KeyCode, key: Key, modifiers: ModifiersState) {
+        let text = match &key {
+            Key::Character(text) if !modifiers.control_key() => Some(text.clone()),
+            _ => None,
+        };
+        editor.set_modifiers(modifiers);
+        editor.key(KeyInput {
+            physical_key: PhysicalKey::Code(code),
+            logical_key: key,
+            text,
+            state: ElementState::Pressed,
+            repeat: false,
+        });
+    }
+
+    fn shown(editor: &Editor) -> String {
+        editor.shown().0.into_iter().collect()
+    }
+
+    #[test]
+    fn selections_survive_modifier_keys_to_be_copied() {
+        let mut editor = editor();
+        let (shift, control) = (ModifiersState::SHIFT, ModifiersState::CONTROL);
+        editor.commit_text("abc");
+
+        press(&mut editor, KeyCode::ShiftLeft, Key::Named(NamedKey::Shift), shift);
+        press(&mut editor, KeyCode::ArrowLeft, Key::Named(NamedKey::ArrowLeft), shift);
+        assert_eq!(editor.selection(), Some(2..3));
+        press(&mut editor, KeyCode::ControlLeft, Key::Named(NamedKey::Control), control);
+        assert_eq!(editor.selection(), Some(2..3));
+        press(&mut editor, KeyCode::KeyC, Key::Character(SmolStr::new("c")), control);
+        assert_eq!((shown(&editor), editor.selection()), ("abc".to_string(), Some(2..3)));
+
+        press(&mut editor, KeyCode::End, Key::Named(NamedKey::End), ModifiersState::empty());
+        press(&mut editor, KeyCode::KeyV, Key::Character(SmolStr::new("v")), control);
+        assert_eq!(editor.shown(), ("abcc".chars().collect(), 4));
+
+        // cutting takes the selection out
+        press(&mut editor, KeyCode::Home, Key::Named(NamedKey::Home), shift);
+        press(&mut editor, KeyCode::KeyX, Key::Character(SmolStr::new("x")), control);
+        press(&mut editor, KeyCode::KeyV, Key::Character(SmolStr::new("v")), control);
+        press(&mut editor, KeyCode::KeyV, Key::Character(SmolStr::new("v")), control);
+        assert_eq!(shown(&editor), "abccabcc");
+    }
+
+    #[test]
+    fn searches_wait_out_modifier_keys() {
+        let mut editor = editor();
+        let (shift, control) = (ModifiersState::SHIFT, ModifiersState::CONTROL);
+        for line in ["Echo one", "ls", "Echo two"] {
+            editor.commit_text(line);
+            press(&mut editor, KeyCode::Enter, Key::Named(NamedKey::Enter), ModifiersState::empty());
+        }
+
+        press(&mut editor, KeyCode::ControlLeft, Key::Named(NamedKey::Control), control);
+        press(&mut editor, KeyCode::KeyR, Key::Character(SmolStr::new("r")), control);
+        press(&mut editor, KeyCode::ShiftLeft, Key::Named(NamedKey::Shift), shift);
+        press(&mut editor, KeyCode::KeyE, Key::Character(SmolStr::new("E")), shift);
+        assert_eq!(shown(&editor), "search: E -> Echo two");
+        press(&mut editor, KeyCode::ControlLeft, Key::Named(NamedKey::Control), control);
+        press(&mut editor, KeyCode::KeyR, Key::Character(SmolStr::new("r")), control);
+        assert_eq!(shown(&editor), "search: E -> Echo one");
+
+        // other keys end the search with the line found, and then act on it
+        press(&mut editor, KeyCode::ArrowLeft, Key::Named(NamedKey::ArrowLeft), ModifiersState::empty());
+        assert!(!editor.is_searching());
+        assert_eq!(editor.shown(), ("Echo one".chars().collect(), 7));
+
+        // and Escape gives back what was typed
+        press(&mut editor, KeyCode::KeyR, Key::Character(SmolStr::new("r")), control);
+        press(&mut editor, KeyCode::KeyL, Key::Character(SmolStr::new("l")), ModifiersState::empty());
+        assert_eq!(shown(&editor), "search: l -> ls");
+        press(&mut editor, KeyCode::Escape, Key::Named(NamedKey::Escape), ModifiersState::empty());
+        assert_eq!(editor.shown(), ("Echo one".chars().collect(), 8));
+    }
+
+    #[test]
+    fn the_search_caret_follows_the_query() {
+        // typing a query with nothing on the input line, before and after a match
+        let (shown, caret) = search_line("ec", "");
+        assert_eq!(shown.iter().collect::<String>(), "search: ec -> ");
+        assert_eq!(shown[..caret].iter().collect::<String>(), "search: ec");
+        let (shown, caret) = search_line("ech", "echo hi");
+        assert_eq!(shown[caret..].iter().collect::<String>(), " -> echo hi");
+
+        let (shown, caret) = search_line(&"x".repeat(MAX_LINE), "");
+        assert_eq!((shown.len(), caret), (MAX_LINE, MAX_LINE));
+    }
+
+    #[test]
+    fn os_text_is_preferred_to_the_key_map() {
+        let a = PhysicalKey::Code(KeyCode::KeyA);
+        let shift = ModifiersState::SHIFT;
+        let character = | text: &str | Key::Character(SmolStr::new(text));
+
+        // a French layout's A key, and a dead circumflex composed with it
+        assert_eq!(typed_text(Some("q"), &character("q"), a, shift), "q");
+        assert_eq!(typed_text(Some("â"), &character("a"), a, shift), "â");
+        // Ctrl+A reports a control character as its text, and unbound Ctrl chords type nothing
+        assert_eq!(typed_text(Some("\u{1}"), &character("a"), a, ModifiersState::CONTROL), "");
+        let q = PhysicalKey::Code(KeyCode::KeyQ);
+        assert_eq!(typed_text(None, &character("q"), q, ModifiersState::CONTROL | shift), "");
+        assert_eq!(typed_text(None, &Key::Dead(Some('^')), PhysicalKey::Code(KeyCode::BracketLeft), shift), "");
+        assert_eq!(typed_text(None, &Key::Unidentified(NativeKey::Unidentified), a, shift), "A");
+        assert_eq!(typed_text(None, &Key::Named(NamedKey::F1), PhysicalKey::Code(KeyCode::F1), shift), "");
+        // the key map's Shift+Tab is a tab, which is not typed any more than the OS's would be
+        let tab = PhysicalKey::Code(KeyCode::Tab);
+        assert_eq!(typed_text(None, &Key::Named(NamedKey::Tab), tab, shift), "");
+        assert_eq!(typed_text(None, &Key::Named(NamedKey::Tab), tab, ModifiersState::empty()), " ");
+    }
+
+    #[test]
+    fn word_jumps_skip_spaces_and_stop_at_word_starts() {
+        let line: Vec<char> = "ls  -la /tmp ".chars().collect();
+
+        assert_eq!(previous_word(&line, 13), 8);
+        assert_eq!(previous_word(&line, 8), 4);
+        assert_eq!(previous_word(&line, 5), 4);
+        assert_eq!(previous_word(&line, 4), 0);
+        assert_eq!(previous_word(&line, 0), 0);
+
+        assert_eq!(next_word(&line, 0), 4);
+        assert_eq!(next_word(&line, 2), 4);
+        assert_eq!(next_word(&line, 5), 8);
+        assert_eq!(next_word(&line, 8), 13);
+        assert_eq!(next_word(&line, 13), 13);
+    }
+}
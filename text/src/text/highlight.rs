@@ -0,0 +1,68 @@
+use glium::{backend::Facade, index::NoIndices, index::PrimitiveType, Program, VertexBuffer};
+use crate::text::char_string::LINE_HEIGHT;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HighlightVertex {
+    pub pos: [f32; 2],
+}
+
+implement_vertex!(HighlightVertex, pos);
+
+/// A solid quad behind a span of glyphs, to show them selected.
+pub struct Highlight {
+    vertices: VertexBuffer<HighlightVertex>,
+    program: Program,
+}
+
+impl Highlight {
+    pub fn new(facade: &dyn Facade) -> Self {
+        Highlight {
+            vertices: VertexBuffer::dynamic(facade, &[Default::default(); 4]).unwrap(),
+            program: Program::from_source(facade, HIGHLIGHT_VS, HIGHLIGHT_FS, None).unwrap(),
+        }
+    }
+
+    pub fn vertices(&self) -> &VertexBuffer<HighlightVertex> {
+        &self.vertices
+    }
+
+    pub fn indices(&self) -> NoIndices {
+        NoIndices(PrimitiveType::TriangleStrip)
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Covers a line from `left` across to `right`.
+    pub fn set_span(&mut self, left: f32, right: f32) {
+        self.vertices.write(&[
+            HighlightVertex { pos: [left, 0.0] },
+            HighlightVertex { pos: [right, 0.0] },
+            HighlightVertex { pos: [left, LINE_HEIGHT] },
+            HighlightVertex { pos: [right, LINE_HEIGHT] },
+        ]);
+    }
+}
+
+const HIGHLIGHT_VS: &str = r#"
+    #version 150
+
+    in vec2 pos;
+
+    uniform vec2 translation;
+
+    void main() {
+        gl_Position = vec4(pos + translation, 0.5, 1.0);
+    }
+"#;
+
+const HIGHLIGHT_FS: &str = r#"
+    #version 150
+
+    out vec4 colour;
+
+    void main() {
+        colour = vec4(0.2, 0.3, 0.6, 1.0);
+    }
+"#;
@@ -0,0 +1,156 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Submitted lines, oldest first and each kept only where it was last used,
+/// along with where Up and Down have browsed to.
+pub struct History {
+    entries: Vec<String>,
+    limit: usize,
+    file: Option<PathBuf>,
+    // the entry being shown, and the line being typed before browsing started
+    browsing: Option<(usize, String)>,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        History {
+            entries: Vec::new(),
+            limit,
+            file: None,
+            browsing: None,
+        }
+    }
+
+    /// Reads the lines `path` holds, if it exists, and appends each line
+    /// submitted from now on to it. The file is rewritten without
+    /// duplicates or the oldest lines beyond the limit when it has any.
+    pub fn load(path: &Path, limit: usize) -> io::Result<Self> {
+        let mut history = History::new(limit);
+        let mut read = 0;
+        match File::open(path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    read += 1;
+                    // skipped as `push` skips them, and so dropped from the file
+                    if !line.trim().is_empty() {
+                        history.remember(line);
+                    }
+                }
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+            Err(error) => return Err(error),
+        }
+
+        if read != history.entries.len() {
+            let mut file = File::create(path)?;
+            for entry in &history.entries {
+                writeln!(file, "{entry}")?;
+            }
+        }
+        history.file = Some(path.to_path_buf());
+
+        Ok(history)
+    }
+
+    /// Adds a submitted line, unless it is blank, and stops browsing.
+    pub fn push(&mut self, line: &str) -> io::Result<()> {
+        self.browsing = None;
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        self.remember(line.to_string());
+
+        match &self.file {
+            Some(path) => writeln!(OpenOptions::new().create(true).append(true).open(path)?, "{line}"),
+            None => Ok(()),
+        }
+    }
+
+    /// The entry before the one shown, given the line currently typed in case browsing starts now.
+    pub fn previous(&mut self, typed: &str) -> Option<&str> {
+        let (index, _) = self.browsing.get_or_insert_with(|| (self.entries.len(), typed.to_string()));
+        *index = index.checked_sub(1)?;
+
+        Some(&self.entries[*index])
+    }
+
+    /// The entry after the one shown, or the line typed before browsing
+    /// once past the newest.
+    pub fn next(&mut self) -> Option<String> {
+        let (index, _) = self.browsing.as_mut()?;
+        *index += 1;
+        if *index < self.entries.len() {
+            return Some(self.entries[*index].clone());
+        }
+
+        self.browsing.take().map(| (_, typed) | typed)
+    }
+
+    /// The newest entry before `before` containing `query`.
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .rposition(| entry | entry.contains(query))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    fn remember(&mut self, line: String) {
+        self.entries.retain(| entry | *entry != line);
+        self.entries.push(line);
+        if self.entries.len() > self.limit {
+            self.entries.remove(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn browsing_returns_to_the_typed_line() {
+        let mut history = History::new(10);
+        for line in ["ls", "cd /", "  ", "ls"] {
+            history.push(line).unwrap();
+        }
+        assert_eq!(history.len(), 2);
+
+        assert_eq!(history.previous("draft"), Some("ls"));
+        assert_eq!(history.previous("ignored"), Some("cd /"));
+        assert_eq!(history.previous("ignored"), None);
+        assert_eq!(history.next().as_deref(), Some("ls"));
+        assert_eq!(history.next().as_deref(), Some("draft"));
+        assert_eq!(history.next(), None);
+
+        assert_eq!(history.search("s", 2), Some(1));
+        assert_eq!(history.search("s", 1), None);
+        assert_eq!(history.search("d", 2), Some(0));
+        assert_eq!(history.get(0), Some("cd /"));
+    }
+
+    #[test]
+    fn history_files_persist_without_duplicates() {
+        let path = std::env::temp_dir().join(format!("text-history-{}", std::process::id()));
+        std::fs::write(&path, "a\nb\n\na\nc\n  \nd\n").unwrap();
+
+        let mut history = History::load(&path, 3).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\nc\nd\n");
+        history.push("e").unwrap();
+        history.push("a").unwrap();
+
+        let history = History::load(&path, 3).unwrap();
+        let entries: Vec<_> = (0..history.len()).filter_map(| i | history.get(i)).collect();
+        assert_eq!(entries, ["d", "e", "a"]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
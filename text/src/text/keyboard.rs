@@ -0,0 +1,173 @@
+use std::time::{Duration, Instant};
+use winit::event::{ElementState, KeyEvent};
+use winit::keyboard::{Key, NamedKey, PhysicalKey, SmolStr};
+
+/// The parts of a `KeyEvent` the console uses, which unlike a `KeyEvent`
+/// can be made up, for repeats and tests.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyInput {
+    pub physical_key: PhysicalKey,
+    pub logical_key: Key,
+    pub text: Option<SmolStr>,
+    pub state: ElementState,
+    pub repeat: bool,
+}
+
+impl From<KeyEvent> for KeyInput {
+    fn from(event: KeyEvent) -> Self {
+        KeyInput {
+            physical_key: event.physical_key,
+            logical_key: event.logical_key,
+            text: event.text,
+            state: event.state,
+            repeat: event.repeat,
+        }
+    }
+}
+
+/// Timing of the repeats made up for a held key.
+#[derive(Clone, Copy, Debug)]
+pub struct RepeatRate {
+    /// From the press to the first repeat.
+    pub delay: Duration,
+    /// Between repeats after that.
+    pub interval: Duration,
+}
+
+impl Default for RepeatRate {
+    fn default() -> Self {
+        RepeatRate {
+            delay: Duration::from_millis(500),
+            interval: Duration::from_millis(33),
+        }
+    }
+}
+
+/// Decides which key inputs are acted on, and repeats held keys on
+/// platforms that do not. Software repeat stops for good at the first
+/// repeat the OS sends itself, and modifier keys are never repeated.
+pub struct KeyRepeat {
+    rate: Option<RepeatRate>,
+    // the key being held and when it next repeats
+    held: Option<(KeyInput, Instant)>,
+}
+
+impl KeyRepeat {
+    pub fn new(rate: Option<RepeatRate>) -> Self {
+        KeyRepeat { rate, held: None }
+    }
+
+    /// Whether `input` should be acted on: presses and the OS's repeats are, releases are not.
+    pub fn accept(&mut self, input: &KeyInput, now: Instant) -> bool {
+        match input.state {
+            ElementState::Released => {
+                if self.held.as_ref().is_some_and(| (held, _) | held.physical_key == input.physical_key) {
+                    self.held = None;
+                }
+                false
+            },
+            ElementState::Pressed if input.repeat => {
+                self.rate = None;
+                self.held = None;
+                true
+            },
+            ElementState::Pressed if is_modifier(&input.logical_key) => true,
+            ElementState::Pressed => {
+                self.held = self.rate.map(| rate | (KeyInput { repeat: true, ..input.clone() }, now + rate.delay));
+                true
+            },
+        }
+    }
+
+    /// Repeats of the held key that were due by `now`.
+    pub fn due(&mut self, now: Instant) -> Vec<KeyInput> {
+        let (Some(rate), Some((input, next))) = (self.rate, &mut self.held) else {
+            return Vec::new();
+        };
+
+        let mut repeats = Vec::new();
+        while *next <= now {
+            repeats.push(input.clone());
+            *next += rate.interval.max(Duration::from_millis(1));
+        }
+
+        repeats
+    }
+}
+
+/// Whether `key` only modifies other keys, and so types nothing by itself.
+pub fn is_modifier(key: &Key) -> bool {
+    matches!(
+        key,
+        Key::Named(NamedKey::Shift | NamedKey::Control | NamedKey::Alt | NamedKey::Super | NamedKey::AltGraph)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::keyboard::KeyCode;
+
+    fn input(code: KeyCode, text: &str, state: ElementState, repeat: bool) -> KeyInput {
+        KeyInput {
+            physical_key: PhysicalKey::Code(code),
+            logical_key: Key::Character(SmolStr::new(text)),
+            text: (state == ElementState::Pressed).then(|| SmolStr::new(text)),
+            state,
+            repeat,
+        }
+    }
+
+    #[test]
+    fn held_keys_repeat_until_released() {
+        let start = Instant::now();
+        let at = | millis | start + Duration::from_millis(millis);
+        let mut repeat = KeyRepeat::new(Some(RepeatRate::default()));
+
+        assert!(repeat.accept(&input(KeyCode::KeyA, "a", ElementState::Pressed, false), start));
+        assert!(repeat.due(at(499)).is_empty());
+        let repeats = repeat.due(at(500));
+        assert_eq!(repeats, [input(KeyCode::KeyA, "a", ElementState::Pressed, true)]);
+        assert_eq!(repeat.due(at(600)).len(), 3);
+
+        // pressing another key takes over, and releasing the first does not stop it
+        assert!(repeat.accept(&input(KeyCode::KeyB, "b", ElementState::Pressed, false), at(610)));
+        assert!(!repeat.accept(&input(KeyCode::KeyA, "a", ElementState::Released, false), at(620)));
+        assert_eq!(repeat.due(at(1110)).len(), 1);
+
+        assert!(!repeat.accept(&input(KeyCode::KeyB, "b", ElementState::Released, false), at(1120)));
+        assert!(repeat.due(at(5000)).is_empty());
+    }
+
+    #[test]
+    fn os_repeats_replace_software_repeats() {
+        let start = Instant::now();
+        let mut repeat = KeyRepeat::new(Some(RepeatRate::default()));
+
+        assert!(repeat.accept(&input(KeyCode::KeyA, "a", ElementState::Pressed, false), start));
+        assert!(repeat.accept(&input(KeyCode::KeyA, "a", ElementState::Pressed, true), start));
+        assert!(repeat.due(start + Duration::from_secs(1)).is_empty());
+
+        assert!(repeat.accept(&input(KeyCode::KeyA, "a", ElementState::Pressed, false), start));
+        assert!(repeat.due(start + Duration::from_secs(1)).is_empty());
+
+        // holding Shift repeats nothing, and leaves a held key repeating
+        let mut repeat = KeyRepeat::new(Some(RepeatRate::default()));
+        let shift = KeyInput {
+            physical_key: PhysicalKey::Code(KeyCode::ShiftLeft),
+            logical_key: Key::Named(NamedKey::Shift),
+            text: None,
+            state: ElementState::Pressed,
+            repeat: false,
+        };
+        assert!(repeat.accept(&shift, start));
+        assert!(repeat.due(start + Duration::from_secs(1)).is_empty());
+        assert!(repeat.accept(&input(KeyCode::KeyA, "a", ElementState::Pressed, false), start));
+        assert!(repeat.accept(&shift, start));
+        assert_eq!(repeat.due(start + Duration::from_millis(500)).len(), 1);
+
+        let mut without = KeyRepeat::new(None);
+        assert!(without.accept(&input(KeyCode::KeyA, "a", ElementState::Pressed, false), start));
+        assert!(without.due(start + Duration::from_secs(1)).is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::VecDeque;
+
+/// Committed lines, oldest first, no more than `width` characters long, and
+/// how far back through them a view of `rows` lines is scrolled.
+pub struct Scrollback {
+    lines: VecDeque<String>,
+    limit: usize,
+    rows: usize,
+    width: usize,
+    offset: usize,
+}
+
+impl Scrollback {
+    pub fn new(limit: usize, rows: usize, width: usize) -> Self {
+        Scrollback {
+            lines: VecDeque::new(),
+            limit,
+            rows,
+            width,
+            offset: 0,
+        }
+    }
+
+    /// Adds a line at the bottom, wrapped onto as many lines as it takes,
+    /// keeping the same lines in view when scrolled back, as far as the
+    /// oldest are not dropped.
+    pub fn push(&mut self, line: String) {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            self.push_wrapped(line);
+        }
+        for part in chars.chunks(self.width.max(1)) {
+            self.push_wrapped(part.iter().collect());
+        }
+    }
+
+    /// Moves the view `lines` further back, or forward when negative,
+    /// without going past either end.
+    pub fn scroll(&mut self, lines: isize) {
+        self.offset = self.offset.saturating_add_signed(lines).min(self.furthest());
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.offset = 0;
+    }
+
+    /// Up to `rows` lines in view, from the bottom up.
+    pub fn visible(&self) -> impl Iterator<Item = &str> {
+        self.lines
+            .iter()
+            .rev()
+            .skip(self.offset)
+            .take(self.rows)
+            .map(String::as_str)
+    }
+
+    fn push_wrapped(&mut self, line: String) {
+        self.lines.push_back(line);
+        if self.offset != 0 {
+            self.offset += 1;
+        }
+        if self.lines.len() > self.limit {
+            self.lines.pop_front();
+            self.offset = self.offset.min(self.furthest());
+        }
+    }
+
+    // The offset that shows the oldest lines in a full view.
+    fn furthest(&self) -> usize {
+        self.lines.len().saturating_sub(self.rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::MAX_LINE;
+
+    #[test]
+    fn scrolling_stays_within_the_lines() {
+        let filled = | rows | {
+            let mut scrollback = Scrollback::new(5, rows, 4);
+            for i in 0..7 {
+                scrollback.push(i.to_string());
+            }
+            scrollback
+        };
+        assert_eq!(filled(10).visible().count(), 5);
+
+        let mut scrollback = filled(3);
+        assert_eq!(scrollback.visible().collect::<Vec<_>>(), ["6", "5", "4"]);
+
+        scrollback.scroll(10);
+        assert_eq!(scrollback.visible().collect::<Vec<_>>(), ["4", "3", "2"]);
+
+        // a new line leaves the view where it was, until the oldest is dropped
+        scrollback.push("7".to_string());
+        assert_eq!(scrollback.visible().collect::<Vec<_>>(), ["5", "4", "3"]);
+
+        scrollback.scroll(-1);
+        assert_eq!(scrollback.visible().collect::<Vec<_>>(), ["6", "5", "4"]);
+        scrollback.scroll(-10);
+        assert_eq!(scrollback.visible().collect::<Vec<_>>(), ["7", "6", "5"]);
+
+        scrollback.push("abcdefghi".to_string());
+        scrollback.push(String::new());
+        assert_eq!(scrollback.visible().collect::<Vec<_>>(), ["", "i", "efgh"]);
+    }
+
+    #[test]
+    fn long_lines_wrap_to_fit_a_row() {
+        // echoing an input line of the longest length adds a prompt to it
+        let mut scrollback = Scrollback::new(10, 4, MAX_LINE);
+        scrollback.push(format!("> {}", "x".repeat(MAX_LINE)));
+        let lines: Vec<&str> = scrollback.visible().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "xx");
+        assert_eq!(lines[1].chars().count(), MAX_LINE);
+    }
+}